@@ -0,0 +1,66 @@
+//! Global allocator used by the test suite to check that the structures free
+//! every node they allocate.
+//!
+//! Allocations are counted per thread, so tests running in parallel do not
+//! interfere with each other's counts.
+//!
+//! Freed blocks are overwritten before they are released, so a node read
+//! after it was freed shows up as garbage in the tests instead of passing by
+//! luck.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ptr;
+
+/// Byte written over every freed block
+const POISON: u8 = 0xA5;
+
+thread_local! {
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+    static TOTAL: Cell<usize> = const { Cell::new(0) };
+}
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LIVE.try_with(|live| live.set(live.get() + 1));
+        let _ = TOTAL.try_with(|total| total.set(total.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let _ = LIVE.try_with(|live| live.set(live.get() + 1));
+        let _ = TOTAL.try_with(|total| total.set(total.get() + 1));
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.try_with(|live| live.set(live.get() - 1));
+        unsafe {
+            // Scribbles over the freed block, so reading it after the free
+            // finds garbage instead of the old values
+            ptr::write_bytes(ptr, POISON, layout.size());
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = TOTAL.try_with(|total| total.set(total.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made by the current thread that were not freed yet
+pub fn live_allocations() -> isize {
+    LIVE.with(|live| live.get())
+}
+
+/// Number of allocations and reallocations made by the current thread so
+/// far, freed or not
+pub fn allocations() -> usize {
+    TOTAL.with(|total| total.get())
+}
@@ -0,0 +1,658 @@
+use std::cmp::{self, Ordering};
+use std::ptr;
+
+use crate::raw_node::{free_subtree, RawNode};
+
+/// Self-balancing Binary Search Tree, that keeps the heights of the two
+/// subtrees of every node within one of each other.
+///
+/// Unlike [`BinarySearchTree`](crate::binary_search_tree::BinarySearchTree),
+/// inserting already sorted data doesn't degrade the tree into a linked list:
+/// search, insert and deletion are always O(log n). Each node stores the
+/// height of its subtree, and rotations restore the balance after every
+/// insertion and deletion.
+///
+/// This implementation utilizes unsafe rust in some places due to the
+/// complexity and runtime overhead of building a compile-time safe structure.
+///
+/// ### Examples
+/// Here are some examples on how to use this structure
+///
+/// #### Inserting an element
+///
+/// ```
+/// use dsa_abc::avl_tree::AvlTree;
+///
+/// // Creates the tree with an initial value of 10
+/// let mut tree = AvlTree::new(10);
+/// // Sorted insertions keep the tree balanced
+/// for data in 11..1000 {
+///     tree.add(data);
+/// }
+/// assert_eq!(tree.get(&500), Some(&500));
+/// assert!(tree.height() <= 15);
+/// ```
+///
+/// #### Deleting an element
+///
+/// ```
+/// use dsa_abc::avl_tree::AvlTree;
+///
+/// let mut tree = AvlTree::new(10);
+/// tree.add(5);
+/// assert_eq!(tree.get(&5), Some(&5));
+///
+/// // deletes the node that contains a value that is equal to 5
+/// tree.delete(&5);
+/// assert_eq!(tree.get(&5), None);
+/// ```
+///
+/// #### Traversal
+///
+/// ```
+/// use dsa_abc::avl_tree::AvlTree;
+///
+/// let mut tree = AvlTree::new(10);
+/// tree.add(5);
+/// tree.add(15);
+///
+/// let mut on_find = |&data| println!("{}", data);
+/// // Prints to console:
+/// // 5
+/// // 10
+/// // 15
+/// tree.in_order(&mut on_find);
+/// ```
+///
+pub struct AvlTree<T: PartialOrd> {
+    root: *mut Node<T>,
+    len: usize,
+}
+
+impl<T: PartialOrd> AvlTree<T> {
+    /// Create a new AVL tree with an initial data as root
+    pub fn new(data: T) -> AvlTree<T> {
+        AvlTree {
+            root: Node::new_mut(data),
+            len: 1,
+        }
+    }
+
+    /// Returns the number of elements in the tree. O(1) time complexity
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree holds no elements. O(1) time complexity
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of levels in the tree, 0 when it is empty.
+    /// O(1) time complexity
+    pub fn height(&self) -> usize {
+        unsafe { height(self.root) }
+    }
+
+    /// Compares `data` with a node's data. `None` means the values can't be
+    /// ordered, like `NaN` for floats: such data is never added, found or
+    /// deleted
+    fn compare(data: &T, node: &T) -> Option<Ordering> {
+        data.partial_cmp(node)
+    }
+
+    /// Add a node recursively with data into the subtree rooted at `node`,
+    /// returning the subtree's new root after rebalancing. `added` is set when
+    /// a node was created
+    unsafe fn add_node(node: *mut Node<T>, data: T, added: &mut bool) -> *mut Node<T> {
+        if node.is_null() {
+            *added = true;
+            return Node::new_mut(data);
+        }
+
+        unsafe {
+            match Self::compare(&data, &(*node).data) {
+                Some(Ordering::Greater) => {
+                    (*node).right = Self::add_node((*node).right, data, added);
+                }
+                Some(Ordering::Less) => (*node).left = Self::add_node((*node).left, data, added),
+                Some(Ordering::Equal) | None => return node,
+            }
+
+            rebalance(node)
+        }
+    }
+
+    /// Add a node to the tree using `data`. If data already exists in tree,
+    /// ignore. O(log n) time complexity
+    pub fn add(&mut self, data: T) {
+        let mut added = false;
+        self.root = unsafe { Self::add_node(self.root, data, &mut added) };
+        if added {
+            self.len += 1;
+        }
+    }
+
+    /// Get a node value for `data` if a node exists with this data. Primarily
+    /// used to check if a given data is present in the tree. O(log n) time
+    /// complexity, O(1) space complexity
+    pub fn get(&self, data: &T) -> Option<&T> {
+        let mut node = self.root;
+
+        unsafe {
+            while !node.is_null() {
+                node = match Self::compare(data, &(*node).data) {
+                    Some(Ordering::Greater) => (*node).right,
+                    Some(Ordering::Less) => (*node).left,
+                    Some(Ordering::Equal) => return Some(&(*node).data),
+                    None => return None,
+                };
+            }
+        }
+
+        None
+    }
+
+    /// Deletes the node holding `data` from the subtree rooted at `node`,
+    /// returning the subtree's new root after rebalancing. The deleted data is
+    /// moved into `removed`
+    unsafe fn delete_node(node: *mut Node<T>, data: &T, removed: &mut Option<T>) -> *mut Node<T> {
+        if node.is_null() {
+            return node;
+        }
+
+        unsafe {
+            match Self::compare(data, &(*node).data) {
+                Some(Ordering::Greater) => {
+                    (*node).right = Self::delete_node((*node).right, data, removed);
+                }
+                Some(Ordering::Less) => {
+                    (*node).left = Self::delete_node((*node).left, data, removed);
+                }
+                None => return node,
+                Some(Ordering::Equal) => {
+                    let left = (*node).left;
+                    let right = (*node).right;
+                    *removed = Some(Box::from_raw(node).data);
+
+                    if left.is_null() {
+                        return right;
+                    } else if right.is_null() {
+                        return left;
+                    }
+
+                    // The successor takes the deleted node's place
+                    let (successor, right) = remove_min(right);
+                    (*successor).left = left;
+                    (*successor).right = right;
+                    return rebalance(successor);
+                }
+            }
+
+            rebalance(node)
+        }
+    }
+
+    /// Deletes the node that holds `data`, if any. O(log n) time complexity
+    pub fn delete(&mut self, data: &T) {
+        let mut removed = None;
+        self.root = unsafe { Self::delete_node(self.root, data, &mut removed) };
+        if removed.is_some() {
+            self.len -= 1;
+        }
+    }
+
+    unsafe fn post_order_node<'a, F>(on_find: &mut F, node: *mut Node<T>)
+    where
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if node.is_null() {
+            return;
+        }
+
+        unsafe {
+            Self::post_order_node(on_find, (*node).left);
+            Self::post_order_node(on_find, (*node).right);
+            on_find(&(*node).data);
+        }
+    }
+
+    unsafe fn pre_order_node<'a, F>(on_find: &mut F, node: *mut Node<T>)
+    where
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if node.is_null() {
+            return;
+        }
+
+        unsafe {
+            on_find(&(*node).data);
+            Self::pre_order_node(on_find, (*node).left);
+            Self::pre_order_node(on_find, (*node).right);
+        }
+    }
+
+    unsafe fn in_order_node<'a, F>(on_find: &mut F, node: *mut Node<T>)
+    where
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if node.is_null() {
+            return;
+        }
+
+        unsafe {
+            Self::in_order_node(on_find, (*node).left);
+            on_find(&(*node).data);
+            Self::in_order_node(on_find, (*node).right);
+        }
+    }
+
+    /// In order traversal with `on_find` callback when each node is found.
+    /// The references passed to `on_find` borrow the tree, so they can't
+    /// outlive it:
+    ///
+    /// ```compile_fail
+    /// use dsa_abc::avl_tree::AvlTree;
+    ///
+    /// let mut values = vec![];
+    /// {
+    ///     let tree = AvlTree::new(1);
+    ///     tree.in_order(&mut |data| values.push(data));
+    /// }
+    /// println!("{values:?}");
+    /// ```
+    pub fn in_order<'a, F>(&'a self, on_find: &mut F)
+    where
+        F: FnMut(&'a T),
+    {
+        unsafe {
+            Self::in_order_node(on_find, self.root);
+        }
+    }
+
+    /// Pre order traversal with `on_find` callback when each node is found
+    pub fn pre_order<'a, F>(&'a self, on_find: &mut F)
+    where
+        F: FnMut(&'a T),
+    {
+        unsafe {
+            Self::pre_order_node(on_find, self.root);
+        }
+    }
+
+    /// Post order traversal with `on_find` callback when each node is found
+    pub fn post_order<'a, F>(&'a self, on_find: &mut F)
+    where
+        F: FnMut(&'a T),
+    {
+        unsafe {
+            Self::post_order_node(on_find, self.root);
+        }
+    }
+
+    /// Returns an iterator over the tree's values in order.
+    /// O(log n) space complexity
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            stack: vec![],
+            remaining: self.len,
+        };
+        iter.push_left(self.root);
+        iter
+    }
+
+    /// Checks the subtree rooted at `node`, returning its height and number of
+    /// nodes. Every value must lie strictly between `low` and `high`
+    unsafe fn check_node(node: *mut Node<T>, low: Option<&T>, high: Option<&T>) -> (usize, usize) {
+        if node.is_null() {
+            return (0, 0);
+        }
+
+        unsafe {
+            let data = &(*node).data;
+            if let Some(low) = low {
+                assert!(data > low, "AVL ordering violated");
+            }
+            if let Some(high) = high {
+                assert!(data < high, "AVL ordering violated");
+            }
+
+            let (left_height, left_len) = Self::check_node((*node).left, low, Some(data));
+            let (right_height, right_len) = Self::check_node((*node).right, Some(data), high);
+
+            assert!(
+                left_height.abs_diff(right_height) <= 1,
+                "AVL balance violated"
+            );
+            let node_height = 1 + cmp::max(left_height, right_height);
+            assert_eq!((*node).height, node_height, "AVL stored height is stale");
+
+            (node_height, 1 + left_len + right_len)
+        }
+    }
+
+    /// Panics if any AVL invariant is broken: the ordering of the values, the
+    /// stored heights, the balance of every node or the tracked length.
+    /// Meant for tests and debugging. O(n) time complexity
+    pub fn assert_invariants(&self) {
+        let (_, len) = unsafe { Self::check_node(self.root, None, None) };
+        assert_eq!(self.len, len, "AVL length is stale");
+    }
+}
+
+impl<T: PartialOrd> Drop for AvlTree<T> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.root);
+        }
+        self.root = ptr::null_mut();
+        self.len = 0;
+    }
+}
+
+impl<'a, T: PartialOrd> IntoIterator for &'a AvlTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// In order iterator over the values of an [`AvlTree`], created by
+/// [`AvlTree::iter`]
+pub struct Iter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<T> Iter<'_, T> {
+    /// Pushes `node` and its chain of left children into the stack
+    fn push_left(&mut self, mut node: *mut Node<T>) {
+        while let Some(current) = unsafe { node.as_ref() } {
+            self.stack.push(current);
+            node = current.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right);
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+struct Node<T> {
+    data: T,
+    left: *mut Node<T>,
+    right: *mut Node<T>,
+    height: usize,
+}
+
+impl<T> Node<T> {
+    fn new(data: T) -> Node<T> {
+        Node {
+            data,
+            left: ptr::null_mut(),
+            right: ptr::null_mut(),
+            height: 1,
+        }
+    }
+
+    fn new_mut(data: T) -> *mut Node<T> {
+        Box::into_raw(Box::new(Self::new(data)))
+    }
+}
+
+impl<T> RawNode for Node<T> {
+    fn children(&mut self) -> (&mut *mut Node<T>, &mut *mut Node<T>) {
+        (&mut self.left, &mut self.right)
+    }
+}
+
+/// Height of the subtree rooted at `node`, 0 for an empty one
+unsafe fn height<T>(node: *mut Node<T>) -> usize {
+    if node.is_null() {
+        0
+    } else {
+        unsafe { (*node).height }
+    }
+}
+
+/// Recomputes the stored height of `node` from its children
+unsafe fn update_height<T>(node: *mut Node<T>) {
+    unsafe {
+        (*node).height = 1 + cmp::max(height((*node).left), height((*node).right));
+    }
+}
+
+/// Rotates the subtree rooted at `node` to the left, returning its new root
+unsafe fn rotate_left<T>(node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        let right = (*node).right;
+        (*node).right = (*right).left;
+        (*right).left = node;
+        update_height(node);
+        update_height(right);
+        right
+    }
+}
+
+/// Rotates the subtree rooted at `node` to the right, returning its new root
+unsafe fn rotate_right<T>(node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        let left = (*node).left;
+        (*node).left = (*left).right;
+        (*left).right = node;
+        update_height(node);
+        update_height(left);
+        left
+    }
+}
+
+/// Updates the height of `node` and rotates its subtree if the heights of its
+/// children differ by more than one, returning the subtree's new root
+unsafe fn rebalance<T>(node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        update_height(node);
+        let left = (*node).left;
+        let right = (*node).right;
+
+        if height(left) > height(right) + 1 {
+            // Left-right case needs the left child rotated first
+            if height((*left).left) < height((*left).right) {
+                (*node).left = rotate_left(left);
+            }
+            rotate_right(node)
+        } else if height(right) > height(left) + 1 {
+            // Right-left case needs the right child rotated first
+            if height((*right).right) < height((*right).left) {
+                (*node).right = rotate_right(right);
+            }
+            rotate_left(node)
+        } else {
+            node
+        }
+    }
+}
+
+/// Detaches the leftmost node of the subtree rooted at `node`, rebalancing
+/// along the way. Returns the detached node and the subtree's new root
+unsafe fn remove_min<T>(node: *mut Node<T>) -> (*mut Node<T>, *mut Node<T>) {
+    unsafe {
+        if (*node).left.is_null() {
+            let right = (*node).right;
+            (*node).right = ptr::null_mut();
+            return (node, right);
+        }
+
+        let (min, left) = remove_min((*node).left);
+        (*node).left = left;
+        (min, rebalance(node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::live_allocations;
+    use crate::xorshift::XorShift;
+    use std::collections::BTreeSet;
+
+    fn sample_tree() -> AvlTree<i32> {
+        let mut tree = AvlTree::new(10);
+        for data in [5, 1, 9, 15, 30, 11] {
+            tree.add(data);
+        }
+        tree
+    }
+
+    #[test]
+    fn find_node() {
+        let tree = sample_tree();
+        assert_eq!(tree.get(&10), Some(&10));
+        assert_eq!(tree.get(&30), Some(&30));
+        assert_eq!(tree.get(&2), None);
+        assert_eq!(tree.len(), 7);
+        tree.assert_invariants();
+    }
+
+    #[test]
+    fn traversals_check() {
+        let tree = sample_tree();
+
+        let mut vals = vec![];
+        tree.in_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, vec![1, 5, 9, 10, 11, 15, 30]);
+        assert!(tree.iter().eq(vals.iter()));
+
+        let mut vals = vec![];
+        tree.pre_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, vec![10, 5, 1, 9, 15, 11, 30]);
+
+        let mut vals = vec![];
+        tree.post_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, vec![1, 9, 5, 11, 30, 15, 10]);
+    }
+
+    #[test]
+    fn rotations_check() {
+        // Right-right, left-left, left-right and right-left cases
+        for order in [[1, 2, 3], [3, 2, 1], [3, 1, 2], [1, 3, 2]] {
+            let mut tree = AvlTree::new(order[0]);
+            tree.add(order[1]);
+            tree.add(order[2]);
+            tree.assert_invariants();
+
+            let mut vals = vec![];
+            tree.pre_order(&mut |&data| vals.push(data));
+            assert_eq!(vals, vec![2, 1, 3]);
+        }
+    }
+
+    #[test]
+    fn delete_check() {
+        let mut tree = sample_tree();
+        tree.delete(&10);
+        tree.assert_invariants();
+        assert_eq!(tree.get(&10), None);
+        tree.delete(&42);
+        assert_eq!(tree.len(), 6);
+
+        for data in [5, 1, 9, 15, 30, 11] {
+            tree.delete(&data);
+            tree.assert_invariants();
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.height(), 0);
+
+        tree.add(3);
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&3]);
+    }
+
+    #[test]
+    fn nan_is_never_matched() {
+        let mut tree = AvlTree::new(2.0);
+        tree.add(1.0);
+        tree.add(3.0);
+        tree.add(f64::NAN);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&f64::NAN), None);
+
+        // Deleting NaN leaves every value, the root included, in place
+        tree.delete(&f64::NAN);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&1.0, &2.0, &3.0]);
+        tree.assert_invariants();
+    }
+
+    #[test]
+    fn sorted_million_keys() {
+        let mut tree = AvlTree::new(0);
+        for data in 1..1_000_000 {
+            tree.add(data);
+        }
+        tree.assert_invariants();
+        assert_eq!(tree.len(), 1_000_000);
+        // An AVL tree with n nodes is at most ~1.44 log2(n) high
+        assert!(tree.height() <= 28);
+        assert_eq!(tree.get(&765_432), Some(&765_432));
+
+        for data in (0..1_000_000).step_by(2) {
+            tree.delete(&data);
+        }
+        tree.assert_invariants();
+        assert_eq!(tree.len(), 500_000);
+        assert!(tree.iter().copied().eq((1..1_000_000).step_by(2)));
+    }
+
+    #[test]
+    fn matches_btreeset() {
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+
+        let mut tree = AvlTree::new(500);
+        let mut expected = BTreeSet::from([500]);
+        for _ in 0..5_000 {
+            let data = rng.next() % 1_000;
+            if rng.next().is_multiple_of(3) {
+                tree.delete(&data);
+                expected.remove(&data);
+            } else {
+                tree.add(data);
+                expected.insert(data);
+            }
+            assert_eq!(tree.get(&data), expected.get(&data));
+        }
+
+        tree.assert_invariants();
+        assert!(tree.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn drop_frees_every_node() {
+        let before = live_allocations();
+        {
+            let mut tree = AvlTree::new(String::from("m"));
+            for data in ["f", "t", "a", "h", "p", "z", "b"] {
+                tree.add(String::from(data));
+            }
+            tree.delete(&String::from("m"));
+            tree.delete(&String::from("a"));
+        }
+        assert_eq!(live_allocations(), before);
+    }
+}
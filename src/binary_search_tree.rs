@@ -1,5 +1,28 @@
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::{Bound, ControlFlow, RangeBounds};
 use std::ptr;
 
+mod comparator;
+mod cursor;
+mod iter;
+pub mod map;
+pub mod multiset;
+mod node_ref;
+
+use crate::raw_node::{free_subtree, RawNode};
+use iter::{IterMut, RawRange};
+
+pub use comparator::{ByKey, Comparator, NaturalOrder, ReverseOrder, TotalOrder};
+pub use cursor::{Cursor, CursorMut};
+pub use iter::{
+    Difference, ExtractIf, Intersection, IntoIter, Iter, LevelOrderDepthIter, LevelOrderIter,
+    PostOrderIter, PreOrderIter, Range, SymmetricDifference, Union,
+};
+pub use map::BstMap;
+pub use multiset::BstMultiset;
+pub use node_ref::NodeRef;
+
 /// Binary Tree most used when you need to quickly search through a set of
 /// ordered values.
 ///
@@ -55,364 +78,1920 @@ use std::ptr;
 /// // 15
 /// tree.in_order(&mut on_find);
 /// ```
+///
+/// #### Custom ordering
+///
+/// Values are ordered by [`PartialOrd`] unless another [`Comparator`] is
+/// given, like [`ReverseOrder`], [`TotalOrder`] or a closure
+///
+/// ```
+/// use dsa_abc::binary_search_tree::{BinarySearchTree, ReverseOrder};
+///
+/// let mut tree = BinarySearchTree::with_comparator(10, ReverseOrder);
+/// tree.add(5);
+/// tree.add(15);
+/// assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&15, &10, &5]);
+/// ```
 /// 
-pub struct BinarySearchTree<T: PartialOrd> {
+pub struct BinarySearchTree<T, C = NaturalOrder> {
     root: *mut Node<T>,
+    comparator: C,
 }
 
-impl<T: PartialOrd + PartialEq + Clone> BinarySearchTree<T> {
+impl<T: PartialOrd> BinarySearchTree<T> {
     /// Create a new BST with an initial data as root
     pub fn new(data: T) -> BinarySearchTree<T> {
+        Self::with_comparator(data, NaturalOrder)
+    }
+
+    /// Create a new empty BST
+    pub fn new_empty() -> BinarySearchTree<T> {
+        Self::default()
+    }
+
+    /// Builds a balanced BST from values in ascending order, linking the nodes
+    /// directly instead of searching for each one. Equal neighbours are kept
+    /// once. O(n) time complexity, O(1) extra space complexity
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value is below the one before it, or can't be ordered
+    /// against it, like NaN
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let tree = BinarySearchTree::from_sorted(1..=7);
+    /// // The middle value ends up at the root
+    /// assert_eq!(tree.pre_order_iter().next(), Some(&4));
+    /// assert_eq!(tree.len(), 7);
+    /// ```
+    pub fn from_sorted<I: IntoIterator<Item = T>>(iter: I) -> BinarySearchTree<T> {
+        Self::from_sorted_by(iter, NaturalOrder)
+    }
+}
+
+impl<T, C: Comparator<T>> BinarySearchTree<T, C> {
+    /// Create a new BST with an initial data as root, ordered by `comparator`
+    /// instead of [`PartialOrd`]
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let case_insensitive = |a: &&str, b: &&str| a.to_lowercase().cmp(&b.to_lowercase());
+    /// let mut tree = BinarySearchTree::with_comparator("b", case_insensitive);
+    /// tree.add("A");
+    /// tree.add("B");
+    ///
+    /// assert_eq!(tree.len(), 2);
+    /// assert_eq!(tree.get(&"a"), Some(&"A"));
+    /// ```
+    pub fn with_comparator(data: T, comparator: C) -> BinarySearchTree<T, C> {
         BinarySearchTree {
             root: Node::new_mut(data),
+            comparator,
         }
     }
 
-    /// Add a node recursively with data. If data already exists in tree, ignore.
-    /// Node must not be root
-    unsafe fn add_node(data: T, node: *mut Node<T>) {
-        unsafe {
-            if data > (*node).data {
-                if (*node).right.is_null() {
-                    (*node).add_right(data)
-                } else {
-                    Self::add_node(data, (*node).right)
-                }
-            } else if data < (*node).data {
-                if (*node).left.is_null() {
-                    (*node).add_left(data)
-                } else {
-                    Self::add_node(data, (*node).left)
-                }
-            }
+    /// Like [`BinarySearchTree::from_sorted`], for values in ascending order
+    /// according to `comparator`
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value is below the one before it, or can't be ordered
+    /// against it, like NaN with [`NaturalOrder`]
+    pub fn from_sorted_by<I>(iter: I, comparator: C) -> BinarySearchTree<T, C>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut vine = Vine::new();
+        let mut iter = iter.into_iter();
+        if vine
+            .extend_sorted(&mut iter, |a, b| comparator.partial_compare(a, b))
+            .is_some()
+        {
+            panic!("values passed to from_sorted are not in ascending order");
+        }
+        BinarySearchTree {
+            root: vine.into_tree(),
+            comparator,
         }
     }
 
+    /// Returns the number of elements in the BST, read from the root's
+    /// subtree size. O(1) time complexity
+    pub fn len(&self) -> usize {
+        unsafe { size(self.root) }
+    }
+
+    /// Returns `true` if the BST holds no elements. O(1) time complexity
+    pub fn is_empty(&self) -> bool {
+        self.root.is_null()
+    }
+
     /// Add a node to the BST using `data`. If data already exists in tree,
-    /// ignore. O(log n) time complexity, O(1) space complexity
+    /// ignore. Values the comparator can't order against the stored ones, like
+    /// NaN with [`NaturalOrder`], are ignored as well; use [`TotalOrder`] to
+    /// keep them. O(log n) time complexity, O(1) space complexity
     pub fn add(&mut self, data: T) {
-        if self.root.is_null() {
-            self.root = Node::new_mut(data);
-        } else {
-            unsafe {
-                Self::add_node(data, self.root);
+        let mut turns = Turns::new();
+        unsafe {
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |node| {
+                self.comparator.partial_compare(&data, node)
+            });
+            if !link.is_null() && (*link).is_null() {
+                *link = Node::new_mut(data);
+                turns.resize(self.root, true);
             }
         }
     }
 
-    /// Get node value from `data`. Primarily used to check if a given data is
-    /// present in the BST
-    unsafe fn get_node<'a>(data: &T, node: *mut Node<T>) -> Option<&'a T> {
-        if node.is_null() {
-            None
-        } else {
-            unsafe {
-                if *data > (*node).data {
-                    Self::get_node(data, (*node).right)
-                } else if *data < (*node).data {
-                    Self::get_node(data, (*node).left)
-                } else {
-                    Some(&(*node).data)
-                }
+    /// Adds `data`, replacing and returning the value that orders equal to
+    /// it, if any. Unlike [`add`](Self::add), the stored value is updated
+    /// even when it's already present, so with a comparator that only looks at
+    /// part of the value the rest of it can be updated. The tree is searched
+    /// once whether the value is swapped or inserted. Like with `add`, a value
+    /// that can't be ordered against the stored ones is dropped. O(log n) time
+    /// complexity, O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::{BinarySearchTree, ByKey};
+    ///
+    /// // (item id, price) pairs, ordered by item id
+    /// let by_id = ByKey(|item: &(u32, u32)| item.0);
+    /// let mut prices = BinarySearchTree::with_comparator((1, 300), by_id);
+    ///
+    /// assert_eq!(prices.replace((1, 450)), Some((1, 300)));
+    /// assert_eq!(prices.replace((2, 120)), None);
+    /// assert_eq!(prices.get(&(1, 0)), Some(&(1, 450)));
+    /// ```
+    pub fn replace(&mut self, data: T) -> Option<T> {
+        let mut turns = Turns::new();
+        unsafe {
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |node| {
+                self.comparator.partial_compare(&data, node)
+            });
+            if link.is_null() {
+                return None;
             }
+            if !(*link).is_null() {
+                return Some(mem::replace(&mut (**link).data, data));
+            }
+
+            *link = Node::new_mut(data);
+            turns.resize(self.root, true);
+            None
         }
     }
 
-    /// Finds a successor to a node, deletes it and returns its value for later
-    /// replacement in another node
-    unsafe fn find_successor_and_delete<'a>(node: *mut Node<T>) -> Option<&'a T> {
-        if (*node).right.is_null() {
-            return None;
-        } else {
-            let mut past = node;
-            let mut current = (*node).right;
+    /// Get a node value for `data` if a node exists with this data. Primarily
+    /// used to check if a given data is present in the BST. A `data` the
+    /// comparator can't order against the stored values, like NaN with
+    /// [`NaturalOrder`], is never found. O(log n) time complexity, O(1) space
+    /// complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let tree = BinarySearchTree::new(1.0);
+    /// assert_eq!(tree.get(&1.0), Some(&1.0));
+    /// assert_eq!(tree.get(&f64::NAN), None);
+    /// ```
+    pub fn get(&self, data: &T) -> Option<&T> {
+        let cmp = |node: &T| self.comparator.partial_compare(data, node);
+        unsafe { find(self.root, cmp).as_ref().map(|node| &node.data) }
+    }
 
-            while !(*current).left.is_null() {
-                past = current;
-                current = (*current).left;
-            }
+    /// Returns a mutable reference to the value equal to `data`, for updating
+    /// the parts of it the comparator doesn't look at, like the payload of a
+    /// tree ordered with [`ByKey`]. O(log n) time complexity
+    ///
+    /// Changing the value in a way that changes its ordering is a logic error:
+    /// it won't cause undefined behavior, but lookups, ranges and removals may
+    /// then miss values or return wrong results
+    pub fn get_mut(&mut self, data: &T) -> Option<&mut T> {
+        let cmp = |node: &T| self.comparator.partial_compare(data, node);
+        unsafe { find(self.root, cmp).as_mut().map(|node| &mut node.data) }
+    }
 
-            if current == (*past).right {
-                (*past).delete_right();
-            } else {
-                (*past).delete_left();
-            }
+    /// Returns the largest value less than or equal to `data`.
+    /// O(log n) time complexity, O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(10);
+    /// tree.add(20);
+    /// tree.add(30);
+    ///
+    /// assert_eq!(tree.floor(&25), Some(&20));
+    /// assert_eq!(tree.floor(&20), Some(&20));
+    /// assert_eq!(tree.floor(&5), None);
+    /// ```
+    pub fn floor(&self, data: &T) -> Option<&T> {
+        unsafe {
+            let cmp = |node: &T| self.comparator.partial_compare(data, node);
+            find_below(self.root, cmp, true)
+                .as_ref()
+                .map(|node| &node.data)
+        }
+    }
 
-            Some(&(*current).data)
+    /// Returns the smallest value greater than or equal to `data`.
+    /// O(log n) time complexity, O(1) space complexity
+    pub fn ceiling(&self, data: &T) -> Option<&T> {
+        unsafe {
+            let cmp = |node: &T| self.comparator.partial_compare(data, node);
+            find_above(self.root, cmp, true)
+                .as_ref()
+                .map(|node| &node.data)
         }
     }
 
-    /// Get a node value for `data` if a node exists with this data. Primarily
-    /// used to check if a given data is present in the BST. O(log n) time
-    /// complexity, O(1) space complexity
-    pub fn get(&self, data: &T) -> Option<&T> {
-        unsafe { Self::get_node(data, self.root) }
-    }
-
-    /// Helper function to delete node
-    unsafe fn delete_node_helper(parent: *mut Node<T>, node: *mut Node<T>, right: bool) {
-        if !(*node).left.is_null() && !(*node).right.is_null() {
-            // We know successor will not be `None`, since we
-            // checked the left and right values for null ptr
-            let successor = Self::find_successor_and_delete(node).unwrap();
-            (*node).data = successor.clone();
-        } else if (*node).left.is_null() && (*node).right.is_null() {
-            if right {
-                (*parent).delete_right();
-            } else {
-                (*parent).delete_left();
-            }
-        } else {
-            // Then right.right is not null
-            if (*node).left.is_null() {
-                (*node).delete_right();
-            } else {
-                (*node).delete_left();
-            }
+    /// Returns the largest value strictly less than `data`. `data` doesn't
+    /// need to be in the BST. O(log n) time complexity, O(1) space complexity
+    pub fn predecessor(&self, data: &T) -> Option<&T> {
+        unsafe {
+            let cmp = |node: &T| self.comparator.partial_compare(data, node);
+            find_below(self.root, cmp, false)
+                .as_ref()
+                .map(|node| &node.data)
         }
     }
 
-    /// Deletes a node. Does not worth if node is root
-    unsafe fn delete_node(data: &T, node: *mut Node<T>) {
-        if node.is_null() {
-            return;
-        } else {
-            unsafe {
-                if *data > (*node).data {
-                    let right = (*node).right;
-                    if right.is_null() {
-                        return;
-                    }
-                    if *data == (*right).data {
-                        Self::delete_node_helper(node, right, true);
-                    } else {
-                        Self::delete_node(data, right);
-                    }
-                } else if *data < (*node).data {
-                    let left = (*node).left;
-                    if left.is_null() {
-                        return;
-                    }
-                    if *data == (*left).data {
-                        Self::delete_node_helper(node, left, false);
-                    } else {
-                        Self::delete_node(data, left);
-                    }
-                }
-            }
+    /// Returns the smallest value strictly greater than `data`. `data` doesn't
+    /// need to be in the BST. O(log n) time complexity, O(1) space complexity
+    pub fn successor(&self, data: &T) -> Option<&T> {
+        unsafe {
+            let cmp = |node: &T| self.comparator.partial_compare(data, node);
+            find_above(self.root, cmp, false)
+                .as_ref()
+                .map(|node| &node.data)
+        }
+    }
+
+    /// Returns the smallest value in the BST. O(log n) time complexity,
+    /// O(1) space complexity
+    pub fn min(&self) -> Option<&T> {
+        unsafe { leftmost(self.root).as_ref().map(|node| &node.data) }
+    }
+
+    /// Returns the largest value in the BST. O(log n) time complexity,
+    /// O(1) space complexity
+    pub fn max(&self) -> Option<&T> {
+        unsafe { rightmost(self.root).as_ref().map(|node| &node.data) }
+    }
+
+    /// Removes the smallest value from the BST and returns it.
+    /// O(log n) time complexity, O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tasks = BinarySearchTree::new((2, "write"));
+    /// tasks.add((1, "plan"));
+    /// tasks.add((3, "ship"));
+    ///
+    /// assert_eq!(tasks.pop_min(), Some((1, "plan")));
+    /// assert_eq!(tasks.pop_min(), Some((2, "write")));
+    /// assert_eq!(tasks.min(), Some(&(3, "ship")));
+    /// ```
+    pub fn pop_min(&mut self) -> Option<T> {
+        if self.root.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let node = unlink_min(ptr::addr_of_mut!(self.root));
+            Some(Box::from_raw(node).data)
+        }
+    }
+
+    /// Removes the largest value from the BST and returns it.
+    /// O(log n) time complexity, O(1) space complexity
+    pub fn pop_max(&mut self) -> Option<T> {
+        if self.root.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let node = unlink_max(ptr::addr_of_mut!(self.root));
+            Some(Box::from_raw(node).data)
         }
     }
 
-    /// Deletes a node. O(log n) time complexity, O(1) space complexity
+    /// Returns the `k`-th smallest value of the BST, counting from zero, or
+    /// `None` if `k` is out of bounds. Subtree sizes let the descent skip
+    /// whole subtrees. O(log n) time complexity, O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(50);
+    /// for data in [10, 90, 30, 70] {
+    ///     tree.add(data);
+    /// }
+    ///
+    /// assert_eq!(tree.select(0), Some(&10));
+    /// // Median of the set
+    /// assert_eq!(tree.select(tree.len() / 2), Some(&50));
+    /// assert_eq!(tree.select(5), None);
+    /// ```
+    pub fn select(&self, k: usize) -> Option<&T> {
+        unsafe { select(self.root, k).as_ref().map(|node| &node.data) }
+    }
+
+    /// Returns how many values of the BST are less than `data`, whether or
+    /// not `data` itself is present. O(log n) time complexity, O(1) space
+    /// complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(50);
+    /// for data in [10, 90, 30, 70] {
+    ///     tree.add(data);
+    /// }
+    ///
+    /// assert_eq!(tree.rank(&10), 0);
+    /// assert_eq!(tree.rank(&70), 3);
+    /// assert_eq!(tree.rank(&60), 3);
+    /// ```
+    pub fn rank(&self, data: &T) -> usize {
+        unsafe { rank(self.root, |node| self.comparator.compare(data, node)) }
+    }
+
+    /// Deletes the node that holds `data`, if any. Works for every node,
+    /// including the root. A `data` the comparator can't order against the
+    /// stored values, like NaN with [`NaturalOrder`], deletes nothing.
+    /// O(log n) time complexity, O(1) space complexity
     pub fn delete(&mut self, data: &T) {
+        let mut turns = Turns::new();
         unsafe {
-            if (*self.root).data == *data {
-                drop(Box::from_raw(self.root));
-                self.root = ptr::null_mut();
-            } else {
-                Self::delete_node(data, self.root);
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |node| {
+                self.comparator.partial_compare(data, node)
+            });
+            if !link.is_null() && !(*link).is_null() {
+                turns.resize(self.root, false);
+                remove_linked(link);
             }
         }
     }
 
-    unsafe fn post_order_node<'a, F>(on_find: &mut F, node: *mut Node<T>)
+    /// In order traversal with `on_find` callback when each node is found.
+    /// Walks an explicit stack instead of recursing, so degenerate trees
+    /// can't overflow the call stack. O(h) space complexity
+    pub fn in_order<'a, F>(&'a self, on_find: &mut F)
     where
         F: FnMut(&'a T),
-        T: 'a,
     {
-        if node.is_null() {
-            return;
-        }
-
-        Self::post_order_node(on_find, (*node).left);
-        Self::post_order_node(on_find, (*node).right);
-        on_find(&(*node).data);
+        self.iter().for_each(on_find);
     }
 
-    unsafe fn pre_order_node<'a, F>(on_find: &mut F, node: *mut Node<T>)
+    /// Pre order traversal with `on_find` callback when each node is found.
+    /// O(h) space complexity
+    pub fn pre_order<'a, F>(&'a self, on_find: &mut F)
     where
         F: FnMut(&'a T),
-        T: 'a,
     {
-        if node.is_null() {
-            return;
-        }
+        self.pre_order_iter().for_each(on_find);
+    }
 
-        on_find(&(*node).data);
-        Self::pre_order_node(on_find, (*node).left);
-        Self::pre_order_node(on_find, (*node).right);
+    /// Post order traversal with `on_find` callback when each node is found.
+    /// O(h) space complexity
+    pub fn post_order<'a, F>(&'a self, on_find: &mut F)
+    where
+        F: FnMut(&'a T),
+    {
+        self.post_order_iter().for_each(on_find);
     }
 
-    unsafe fn in_order_node<'a, F>(on_find: &mut F, node: *mut Node<T>)
+    /// Level order traversal with `on_find` callback when each node is found,
+    /// from the root down and from left to right. O(n) space complexity
+    pub fn level_order<'a, F>(&'a self, on_find: &mut F)
     where
         F: FnMut(&'a T),
-        T: 'a,
     {
-        if node.is_null() {
-            return;
-        }
+        self.level_order_iter().for_each(on_find);
+    }
 
-        Self::in_order_node(on_find, (*node).left);
-        on_find(&(*node).data);
-        Self::in_order_node(on_find, (*node).right);
+    /// In order traversal that stops as soon as `on_find` returns
+    /// [`ControlFlow::Break`], returning the break value. Nodes after it are
+    /// never visited. O(h) space complexity
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let tree: BinarySearchTree<i32> = [8, 3, 10, 6, 14].into_iter().collect();
+    ///
+    /// let mut visited = 0;
+    /// let first_even = tree.try_in_order(&mut |&value| {
+    ///     visited += 1;
+    ///     if value % 2 == 0 {
+    ///         ControlFlow::Break(value)
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(first_even, ControlFlow::Break(6));
+    /// assert_eq!(visited, 2);
+    /// ```
+    pub fn try_in_order<'a, B, F>(&'a self, on_find: &mut F) -> ControlFlow<B>
+    where
+        F: FnMut(&'a T) -> ControlFlow<B>,
+    {
+        self.iter().try_for_each(on_find)
     }
 
-    /// In order traversal with `on_find` callback when each node is found
-    pub fn in_order<'a, F>(&self, on_find: &mut F)
+    /// Pre order traversal that stops as soon as `on_find` returns
+    /// [`ControlFlow::Break`], returning the break value. O(h) space complexity
+    pub fn try_pre_order<'a, B, F>(&'a self, on_find: &mut F) -> ControlFlow<B>
     where
-        F: FnMut(&'a T),
-        T: 'a,
+        F: FnMut(&'a T) -> ControlFlow<B>,
     {
-        unsafe {
-            Self::in_order_node(on_find, self.root);
-        }
+        self.pre_order_iter().try_for_each(on_find)
     }
 
-    /// Pre order traversal with `on_find` callback when each node is found
-    pub fn pre_order<'a, F>(&self, on_find: &mut F)
+    /// Post order traversal that stops as soon as `on_find` returns
+    /// [`ControlFlow::Break`], returning the break value. O(h) space complexity
+    pub fn try_post_order<'a, B, F>(&'a self, on_find: &mut F) -> ControlFlow<B>
     where
-        F: FnMut(&'a T),
-        T: 'a,
+        F: FnMut(&'a T) -> ControlFlow<B>,
     {
-        unsafe {
-            Self::pre_order_node(on_find, self.root);
-        }
+        self.post_order_iter().try_for_each(on_find)
     }
 
-    /// Post order traversal with `on_find` callback when each node is found
-    pub fn post_order<'a, F>(&self, on_find: &mut F)
+    /// In order traversal with `on_find` callback receiving a mutable
+    /// reference to each value, for updating the parts of a value the
+    /// comparator doesn't look at. O(h) space complexity
+    ///
+    /// Changing a value in a way that changes its ordering is a logic error:
+    /// it won't cause undefined behavior, but lookups, ranges and removals may
+    /// then miss values or return wrong results
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// struct Account {
+    ///     id: u32,
+    ///     balance: i64,
+    /// }
+    ///
+    /// let by_id = |a: &Account, b: &Account| a.id.cmp(&b.id);
+    /// let mut accounts = BinarySearchTree::with_comparator(Account { id: 2, balance: 10 }, by_id);
+    /// accounts.add(Account { id: 1, balance: 5 });
+    ///
+    /// accounts.in_order_mut(&mut |account| account.balance *= 2);
+    ///
+    /// let balances: Vec<_> = accounts.iter().map(|account| account.balance).collect();
+    /// assert_eq!(balances, vec![10, 20]);
+    /// ```
+    pub fn in_order_mut<F>(&mut self, on_find: &mut F)
     where
-        F: FnMut(&'a T),
-        T: 'a,
+        F: FnMut(&mut T),
     {
-        unsafe {
-            Self::post_order_node(on_find, self.root);
-        }
+        IterMut::new(self.root, self.len()).for_each(on_find);
     }
-}
 
-pub struct Node<T: PartialOrd> {
-    data: T,
-    left: *mut Node<T>,
-    right: *mut Node<T>,
-}
+    /// In order traversal in O(1) space with Morris threading: before
+    /// descending into a left subtree, the empty right link of its largest
+    /// node is pointed back at the current node, and removed when the walk
+    /// comes back through it. Needs `&mut self` since links are rewritten
+    /// during the walk, but the tree is left as it was, even if `on_find`
+    /// panics. O(n) time complexity, O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(2);
+    /// tree.add(1);
+    /// tree.add(3);
+    ///
+    /// let mut values = vec![];
+    /// tree.in_order_morris(&mut |&data| values.push(data));
+    /// assert_eq!(values, vec![1, 2, 3]);
+    /// ```
+    pub fn in_order_morris<F>(&mut self, on_find: &mut F)
+    where
+        F: FnMut(&T),
+    {
+        /// Finishes the walk if `on_find` panics, removing leftover threads
+        struct Walk<T>(*mut Node<T>);
 
-impl<T: PartialOrd> Node<T> {
-    fn new(data: T) -> Node<T> {
-        Node {
-            data,
-            left: ptr::null_mut(),
-            right: ptr::null_mut(),
+        impl<T> Drop for Walk<T> {
+            fn drop(&mut self) {
+                while !self.0.is_null() {
+                    self.0 = unsafe { morris_step(self.0).0 };
+                }
+            }
+        }
+
+        let mut walk = Walk(self.root);
+        while !walk.0.is_null() {
+            let (next, visit) = unsafe { morris_step(walk.0) };
+            walk.0 = next;
+            if let Some(node) = unsafe { visit.as_ref() } {
+                on_find(&node.data);
+            }
         }
     }
 
-    fn new_mut(data: T) -> *mut Node<T> {
-        Box::into_raw(Box::new(Self::new(data)))
+    /// Returns an iterator over the BST's values in order. Iterating from both
+    /// ends is supported. O(h) space complexity
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self.root, self.len())
     }
 
-    fn add_left(&mut self, data: T) {
-        if !self.left.is_null() {
-            return;
+    /// Returns an iterator over the BST's values in pre order.
+    /// O(h) space complexity
+    pub fn pre_order_iter(&self) -> PreOrderIter<'_, T> {
+        PreOrderIter::new(self.root, self.len())
+    }
+
+    /// Returns an iterator over the BST's values in post order.
+    /// O(h) space complexity
+    pub fn post_order_iter(&self) -> PostOrderIter<'_, T> {
+        PostOrderIter::new(self.root, self.len())
+    }
+
+    /// Returns an iterator over the BST's values level by level, from the root
+    /// down and from left to right. O(n) space complexity
+    pub fn level_order_iter(&self) -> LevelOrderIter<'_, T> {
+        LevelOrderIter::new(self.root, self.len())
+    }
+
+    /// Returns an iterator over the BST's values level by level, like
+    /// [`level_order_iter`](Self::level_order_iter), paired with their depth.
+    /// The root is at depth 0. O(n) space complexity
+    pub fn level_order_depth_iter(&self) -> LevelOrderDepthIter<'_, T> {
+        LevelOrderDepthIter::new(self.root, self.len())
+    }
+
+    /// Returns the BST's values grouped by depth, each level from left to
+    /// right. Empty subtrees leave no gaps, so a level only holds the values
+    /// that are in the tree. O(n) time and space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(10);
+    /// for value in [5, 15, 1, 30] {
+    ///     tree.add(value);
+    /// }
+    ///
+    /// assert_eq!(tree.levels(), vec![vec![&10], vec![&5, &15], vec![&1, &30]]);
+    /// ```
+    pub fn levels(&self) -> Vec<Vec<&T>> {
+        let mut levels: Vec<Vec<&T>> = Vec::new();
+        for (depth, data) in self.level_order_depth_iter() {
+            if depth == levels.len() {
+                levels.push(Vec::new());
+            }
+            levels[depth].push(data);
         }
-        self.left = Self::new_mut(data)
+        levels
     }
 
-    fn add_right(&mut self, data: T) {
-        if !self.right.is_null() {
-            return;
+    /// Rebuilds the BST in place into a balanced shape, reusing its nodes. Useful
+    /// after adding values in sorted order, which leaves a degenerate spine.
+    /// O(n) time complexity, O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(1);
+    /// for data in 2..=7 {
+    ///     tree.add(data);
+    /// }
+    /// assert_eq!(tree.pre_order_iter().next(), Some(&1));
+    ///
+    /// tree.rebalance();
+    /// assert_eq!(tree.pre_order_iter().next(), Some(&4));
+    /// ```
+    pub fn rebalance(&mut self) {
+        let len = self.len();
+        unsafe {
+            flatten(ptr::addr_of_mut!(self.root));
+            balance_vine(ptr::addr_of_mut!(self.root), len);
         }
-        self.right = Self::new_mut(data)
     }
 
-    fn delete_left(&mut self) {
+    /// Keeps only the values for which `keep` returns `true`, visiting them in
+    /// order. The tree is flattened, walked once, and rebuilt balanced from
+    /// the kept nodes, so it is left balanced. O(n) time complexity, O(1)
+    /// space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::from_sorted(1..=10);
+    /// tree.retain(|&value| value % 3 == 0);
+    ///
+    /// assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&3, &6, &9]);
+    /// ```
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.extract_if(|data| !keep(data)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes the values for which `pred` returns
+    /// `true` and yields them in order. Values the iterator doesn't reach
+    /// before being dropped are kept. The tree is rebuilt balanced once the
+    /// iterator is dropped. O(n) time complexity, O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut cache = BinarySearchTree::from_sorted(1..=8);
+    /// let expired: Vec<_> = cache.extract_if(|&stamp| stamp <= 3).collect();
+    ///
+    /// assert_eq!(expired, vec![1, 2, 3]);
+    /// assert_eq!(cache.len(), 5);
+    /// ```
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, T, F>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let len = self.len();
+        ExtractIf::new(&mut self.root, len, pred)
+    }
+
+    /// Removes every value and returns them in order as an owning iterator.
+    /// The tree is empty right away, even if the iterator isn't consumed.
+    /// O(1) space complexity
+    pub fn drain(&mut self) -> IntoIter<T> {
+        let len = self.len();
+        IntoIter::new(mem::replace(&mut self.root, ptr::null_mut()), len)
+    }
+
+    /// Moves every value greater than or equal to `data` into a new BST and
+    /// returns it, keeping the smaller ones. Nodes are relinked along a single
+    /// search path instead of being added again. O(log n) time complexity,
+    /// O(1) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut low = BinarySearchTree::from_sorted(1..=6);
+    /// let high = low.split_off(&4);
+    ///
+    /// assert_eq!(low.iter().collect::<Vec<_>>(), vec![&1, &2, &3]);
+    /// assert_eq!(high.iter().collect::<Vec<_>>(), vec![&4, &5, &6]);
+    /// ```
+    pub fn split_off(&mut self, data: &T) -> BinarySearchTree<T, C>
+    where
+        C: Clone,
+    {
+        // Knowing how many values of each subtree fall below `data` lets the
+        // sizes be fixed on the way down
+        let mut below = self.rank(data);
+        let mut total = self.len();
+        let mut low = ptr::null_mut();
+        let mut high = ptr::null_mut();
+        let mut low_link = ptr::addr_of_mut!(low);
+        let mut high_link = ptr::addr_of_mut!(high);
+
         unsafe {
-            drop(Box::from_raw(self.left));
-            self.left = ptr::null_mut()
+            let mut node = self.root;
+            while !node.is_null() {
+                if self.comparator.compare(data, &(*node).data) == Ordering::Greater {
+                    (*node).size = below;
+                    below -= size((*node).left) + 1;
+                    total = size((*node).right);
+                    *low_link = node;
+                    low_link = ptr::addr_of_mut!((*node).right);
+                    node = (*node).right;
+                } else {
+                    (*node).size = total - below;
+                    total = size((*node).left);
+                    *high_link = node;
+                    high_link = ptr::addr_of_mut!((*node).left);
+                    node = (*node).left;
+                }
+            }
+
+            *low_link = ptr::null_mut();
+            *high_link = ptr::null_mut();
+        }
+
+        self.root = low;
+        BinarySearchTree {
+            root: high,
+            comparator: self.comparator.clone(),
         }
     }
 
-    fn delete_right(&mut self) {
+    /// Moves every value of `other` into `self`, leaving `other` empty. When
+    /// all of `other` lies on one side of `self`, the trees are joined under a
+    /// single node in O(log n + log m). Otherwise both are merged into a
+    /// balanced tree in O(n + m). Values already in `self` are kept
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::from_sorted([1, 2, 3]);
+    /// let mut other = BinarySearchTree::from_sorted([4, 5, 6]);
+    /// tree.append(&mut other);
+    ///
+    /// assert_eq!(tree.len(), 6);
+    /// assert!(other.is_empty());
+    /// ```
+    pub fn append(&mut self, other: &mut BinarySearchTree<T, C>) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            mem::swap(&mut self.root, &mut other.root);
+            return;
+        }
+
         unsafe {
-            drop(Box::from_raw(self.right));
-            self.right = ptr::null_mut()
+            let (min, max) = (self.min().unwrap(), self.max().unwrap());
+            if self.comparator.compare(max, other.min().unwrap()) == Ordering::Less {
+                let pivot = unlink_min(ptr::addr_of_mut!(other.root));
+                let right = mem::replace(&mut other.root, ptr::null_mut());
+                self.root = join_nodes(self.root, pivot, right);
+            } else if self.comparator.compare(min, other.max().unwrap()) == Ordering::Greater {
+                let pivot = unlink_min(ptr::addr_of_mut!(self.root));
+                let left = mem::replace(&mut other.root, ptr::null_mut());
+                self.root = join_nodes(left, pivot, self.root);
+            } else {
+                // Both trees are emptied before merging, so a panicking
+                // comparator can't leave them pointing at freed nodes
+                flatten(ptr::addr_of_mut!(self.root));
+                flatten(ptr::addr_of_mut!(other.root));
+                let own = mem::replace(&mut self.root, ptr::null_mut());
+                let other = mem::replace(&mut other.root, ptr::null_mut());
+                let comparator = &self.comparator;
+                let vine = merge_vines(own, other, |a, b| comparator.compare(a, b));
+                self.root = vine.into_tree();
+            }
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Builds a BST with `pivot` at the root and the nodes of `left` and
+    /// `right` below it, without moving any of them. O(log n + log m) time
+    /// complexity, spent checking the ordering
+    ///
+    /// # Panics
+    ///
+    /// Panics unless every value of `left` is below `pivot` and every value
+    /// of `right` is above it
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let left = BinarySearchTree::from_sorted([1, 2]);
+    /// let right = BinarySearchTree::from_sorted([4, 5]);
+    /// let tree = BinarySearchTree::join(left, 3, right);
+    ///
+    /// assert_eq!(tree.pre_order_iter().next(), Some(&3));
+    /// assert_eq!(tree.len(), 5);
+    /// ```
+    pub fn join(
+        mut left: BinarySearchTree<T, C>,
+        pivot: T,
+        mut right: BinarySearchTree<T, C>,
+    ) -> BinarySearchTree<T, C> {
+        let comparator = &left.comparator;
+        let ordered = left
+            .max()
+            .is_none_or(|max| comparator.compare(max, &pivot) == Ordering::Less)
+            && right
+                .min()
+                .is_none_or(|min| comparator.compare(min, &pivot) == Ordering::Greater);
+        if !ordered {
+            panic!("join needs every value of left below pivot and every value of right above it");
+        }
 
-    #[test]
-    fn find_root() {
-        let tree = BinarySearchTree::new(10);
-        assert_eq!(tree.get(&10), Some(&10));
-        assert_eq!(tree.get(&9), None);
+        let root = mem::replace(&mut left.root, ptr::null_mut());
+        let right = mem::replace(&mut right.root, ptr::null_mut());
+        left.root = unsafe { join_nodes(root, Node::new_mut(pivot), right) };
+        left
     }
 
-    #[test]
-    fn find_node() {
-        let mut tree = BinarySearchTree::new(10);
-        tree.add(5);
-        tree.add(15);
-        assert_eq!(tree.get(&10), Some(&10));
-        assert_eq!(tree.get(&5), Some(&5));
-        assert_eq!(tree.get(&15), Some(&15));
+    /// Returns an iterator over the BST's values within `bounds`, in order.
+    /// Iterating from both ends is supported. Only the subtrees that overlap
+    /// the range are visited: O(log n + k) time complexity for k values
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is above its end, or if both ends are
+    /// excluded and equal
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(10);
+    /// for data in [5, 1, 9, 15, 30, 11] {
+    ///     tree.add(data);
+    /// }
+    ///
+    /// assert_eq!(tree.range(5..11).collect::<Vec<_>>(), vec![&5, &9, &10]);
+    /// assert_eq!(tree.range(..=9).next_back(), Some(&9));
+    /// ```
+    pub fn range<R: RangeBounds<T>>(&self, bounds: R) -> Range<'_, T> {
+        let cmp = |a: &T, b: &T| self.comparator.compare(a, b);
+        check_bounds(bounds.start_bound(), bounds.end_bound(), cmp);
+        Range::new(RawRange::new(
+            self.root,
+            |data| is_before(data, bounds.start_bound(), cmp),
+            |data| is_after(data, bounds.end_bound(), cmp),
+        ))
     }
 
-    #[test]
-    fn find_deleted_root() {
-        let mut tree = BinarySearchTree::new(10);
+    /// Returns how many values lie within `bounds`, without visiting them:
+    /// the count is the difference between the ranks of both ends.
+    /// O(log n) time complexity, O(1) space complexity
+    ///
+    /// # Panics
+    ///
+    /// Panics like [`range`](Self::range) on invalid bounds
+    pub fn count_range<R: RangeBounds<T>>(&self, bounds: R) -> usize {
+        let cmp = |a: &T, b: &T| self.comparator.compare(a, b);
+        check_bounds(bounds.start_bound(), bounds.end_bound(), cmp);
+        unsafe { count_between(self.root, bounds.start_bound(), bounds.end_bound(), cmp) }
+    }
 
-        tree.add(5);
-        assert_eq!(tree.get(&5), Some(&5));
+    /// Returns a lazy iterator over the values in `self` or `other`, in order
+    /// and without duplicates. Both trees are walked once in order, so a full
+    /// pass takes O(n + m) time
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let a = BinarySearchTree::from_sorted([1, 2, 3]);
+    /// let b = BinarySearchTree::from_sorted([2, 3, 4]);
+    ///
+    /// assert_eq!(a.union(&b).collect::<Vec<_>>(), vec![&1, &2, &3, &4]);
+    /// assert_eq!(a.intersection(&b).collect::<Vec<_>>(), vec![&2, &3]);
+    /// assert_eq!(a.difference(&b).collect::<Vec<_>>(), vec![&1]);
+    /// assert_eq!(
+    ///     a.symmetric_difference(&b).collect::<Vec<_>>(),
+    ///     vec![&1, &4]
+    /// );
+    /// ```
+    pub fn union<'a>(&'a self, other: &'a BinarySearchTree<T, C>) -> Union<'a, T, C> {
+        Union::new(self.iter(), other.iter(), &self.comparator)
+    }
 
-        tree.delete(&5);
-        assert_eq!(tree.get(&5), None);
+    /// Returns a lazy iterator over the values in both `self` and `other`, in
+    /// order. O(n + m) time complexity for a full pass
+    pub fn intersection<'a>(&'a self, other: &'a BinarySearchTree<T, C>) -> Intersection<'a, T, C> {
+        Intersection::new(self.iter(), other.iter(), &self.comparator)
+    }
 
-        tree.add(15);
-        tree.delete(&15);
-        assert_eq!(tree.get(&15), None);
+    /// Returns a lazy iterator over the values in `self` but not in `other`,
+    /// in order. O(n + m) time complexity for a full pass
+    pub fn difference<'a>(&'a self, other: &'a BinarySearchTree<T, C>) -> Difference<'a, T, C> {
+        Difference::new(self.iter(), other.iter(), &self.comparator)
+    }
 
-        tree.add(5);
-        tree.add(1);
-        tree.add(9);
-        tree.delete(&5);
-        assert_eq!(tree.get(&5), None);
+    /// Returns a lazy iterator over the values in exactly one of `self` and
+    /// `other`, in order. O(n + m) time complexity for a full pass
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a BinarySearchTree<T, C>,
+    ) -> SymmetricDifference<'a, T, C> {
+        SymmetricDifference::new(self.iter(), other.iter(), &self.comparator)
+    }
 
-        let mut vals: Vec<i32> = vec![];
-        let mut on_find = |&data| vals.push(data);
-        tree.in_order(&mut on_find);
-        assert_eq!(vals.get(0), Some(&1));
-        assert_eq!(vals.get(1), Some(&9));
-        assert_eq!(vals.get(2), Some(&10));
+    /// Returns `true` if every value of `self` is also in `other`.
+    /// O(n + m) time complexity
+    pub fn is_subset(&self, other: &BinarySearchTree<T, C>) -> bool {
+        self.len() <= other.len() && self.difference(other).next().is_none()
     }
 
-    #[test]
-    fn find_deleted_node() {
-        let mut tree = BinarySearchTree::new(10);
+    /// Returns `true` if every value of `other` is also in `self`.
+    /// O(n + m) time complexity
+    pub fn is_superset(&self, other: &BinarySearchTree<T, C>) -> bool {
+        other.is_subset(self)
+    }
 
-        tree.add(20);
-        assert_eq!(tree.get(&20), Some(&20));
+    /// Returns `true` if `self` and `other` have no value in common.
+    /// O(n + m) time complexity
+    pub fn is_disjoint(&self, other: &BinarySearchTree<T, C>) -> bool {
+        self.intersection(other).next().is_none()
+    }
 
-        tree.delete(&20);
-        assert_eq!(tree.get(&20), None);
+    /// Returns a handle to the root node, or `None` if the BST is empty. The
+    /// handle can walk down to every other node, and borrows the tree so it
+    /// can't outlive a change to it. O(1) time complexity
+    pub fn root(&self) -> Option<NodeRef<'_, T>> {
+        NodeRef::new(self.root)
     }
 
-    #[test]
-    fn in_order_check() {
-        let mut vals: Vec<i32> = vec![];
-        let mut tree = BinarySearchTree::new(10);
-        tree.add(5);
-        tree.add(1);
-        tree.add(9);
-        tree.add(15);
-        tree.add(30);
-        tree.add(11);
+    /// Returns the number of levels in the BST, 0 when it is empty.
+    /// O(n) time complexity, O(h) space complexity
+    pub fn height(&self) -> usize {
+        fold_subtrees(self.root, 0, |_, left, right| left.max(right) + 1)
+    }
 
-        let mut func = |&data| vals.push(data);
-        tree.in_order(&mut func);
-        assert_eq!(vals.get(0), Some(&1));
+    /// Returns `true` if the heights of the two subtrees of every node differ
+    /// by at most one, the balance an AVL tree keeps.
+    /// O(n) time complexity, O(h) space complexity
+    pub fn is_balanced(&self) -> bool {
+        fold_subtrees(self.root, Some(0usize), |_, left, right| {
+            let (left, right) = (left?, right?);
+            (left.abs_diff(right) <= 1).then_some(left.max(right) + 1)
+        })
+        .is_some()
+    }
+
+    /// Returns `true` if the nodes are linked in a valid BST: walking the
+    /// `left` and `right` pointers in order yields strictly ascending values,
+    /// and every cached subtree size matches the nodes below it.
+    /// O(n) time complexity, O(h) space complexity
+    pub fn is_valid_bst(&self) -> bool {
+        let sizes_match = fold_subtrees(self.root, Some(0), |node, left, right| {
+            let size = left? + right? + 1;
+            (size == node.size).then_some(size)
+        })
+        .is_some();
+
+        // The in order walk trusts the sizes, so they are checked first
+        sizes_match
+            && self
+                .iter()
+                .zip(self.iter().skip(1))
+                .all(|(a, b)| self.comparator.compare(a, b) == Ordering::Less)
+    }
+
+    /// Reports the shape of the BST: how many nodes sit at each depth, how
+    /// many are leaves and how long searches are on average.
+    /// O(n) time complexity, O(h) space complexity
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BinarySearchTree;
+    ///
+    /// let mut tree = BinarySearchTree::new(2);
+    /// tree.add(1);
+    /// tree.add(3);
+    /// tree.add(4);
+    ///
+    /// let stats = tree.shape_stats();
+    /// assert_eq!(stats.depth_histogram, vec![1, 2, 1]);
+    /// assert_eq!(stats.leaves, 2);
+    /// // (1 + 2 + 2 + 3) nodes visited over 4 searches
+    /// assert_eq!(stats.average_search_path, 2.0);
+    /// ```
+    pub fn shape_stats(&self) -> ShapeStats {
+        let mut depth_histogram = vec![];
+        let mut leaves = 0;
+        let mut path_total = 0;
+        let mut stack = vec![];
+        if !self.root.is_null() {
+            stack.push((self.root, 0));
+        }
+
+        while let Some((node, depth)) = stack.pop() {
+            if depth_histogram.len() == depth {
+                depth_histogram.push(0);
+            }
+            depth_histogram[depth] += 1;
+            path_total += depth + 1;
+
+            let (left, right) = unsafe { ((*node).left, (*node).right) };
+            if left.is_null() && right.is_null() {
+                leaves += 1;
+            }
+            for child in [right, left] {
+                if !child.is_null() {
+                    stack.push((child, depth + 1));
+                }
+            }
+        }
+
+        let len = self.len();
+        ShapeStats {
+            len,
+            height: depth_histogram.len(),
+            leaves,
+            depth_histogram,
+            average_search_path: if len == 0 {
+                0.0
+            } else {
+                path_total as f64 / len as f64
+            },
+        }
+    }
+}
+
+impl<T, K, F> BinarySearchTree<T, ByKey<F>>
+where
+    K: PartialOrd,
+    F: Fn(&T) -> K,
+{
+    /// Returns the value whose key equals `key`. O(log n) time complexity
+    pub fn get_by_key(&self, key: &K) -> Option<&T> {
+        let cmp = |node: &T| key.partial_cmp(&(self.comparator.0)(node));
+        unsafe { find(self.root, cmp).as_ref().map(|node| &node.data) }
+    }
+
+    /// Returns a mutable reference to the value whose key equals `key`. Like
+    /// [`get_mut`](Self::get_mut), changing the key through it is a logic
+    /// error. O(log n) time complexity
+    pub fn get_mut_by_key(&mut self, key: &K) -> Option<&mut T> {
+        let cmp = |node: &T| key.partial_cmp(&(self.comparator.0)(node));
+        unsafe { find(self.root, cmp).as_mut().map(|node| &mut node.data) }
+    }
+}
+
+impl<T, C> IntoIterator for BinarySearchTree<T, C> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    /// Consumes the BST into an iterator over its values in order.
+    /// O(1) space complexity
+    fn into_iter(mut self) -> IntoIter<T> {
+        let len = unsafe { size(self.root) };
+        let root = mem::replace(&mut self.root, ptr::null_mut());
+        IntoIter::new(root, len)
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a BinarySearchTree<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        Iter::new(self.root, unsafe { size(self.root) })
+    }
+}
+
+impl<T, C: Default> Default for BinarySearchTree<T, C> {
+    /// Creates an empty BST ordered by the comparator's default value
+    fn default() -> BinarySearchTree<T, C> {
+        BinarySearchTree {
+            root: ptr::null_mut(),
+            comparator: C::default(),
+        }
+    }
+}
+
+impl<T, C: Comparator<T> + Default> FromIterator<T> for BinarySearchTree<T, C> {
+    /// Builds a BST from `iter`. The leading run of values in ascending order
+    /// is linked as a balanced tree in O(n), like
+    /// [`BinarySearchTree::from_sorted`]; values after it, starting with the
+    /// first one out of order or that can't be ordered, are added one by one
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> BinarySearchTree<T, C> {
+        let comparator = C::default();
+        let mut iter = iter.into_iter();
+        let mut vine = Vine::new();
+        let rest = vine.extend_sorted(&mut iter, |a, b| comparator.partial_compare(a, b));
+
+        let mut tree = BinarySearchTree {
+            root: vine.into_tree(),
+            comparator,
+        };
+        for data in rest.into_iter().chain(iter) {
+            tree.add(data);
+        }
+        tree
+    }
+}
+
+impl<T, C: Comparator<T>> Extend<T> for BinarySearchTree<T, C> {
+    /// Adds every value of `iter` to the BST. When the values come in
+    /// ascending order and are at least as many as the stored ones, both
+    /// sequences are merged and rebuilt into a balanced tree in O(n + m);
+    /// otherwise they are added one by one
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let values: Vec<T> = iter.into_iter().collect();
+        let sorted = values.windows(2).all(|pair| {
+            let order = self.comparator.partial_compare(&pair[0], &pair[1]);
+            matches!(order, Some(Ordering::Less | Ordering::Equal))
+        });
+
+        if !sorted || values.len() < self.len() {
+            for data in values {
+                self.add(data);
+            }
+            return;
+        }
+
+        let comparator = &self.comparator;
+        let mut values = values.into_iter();
+        let mut incoming = Vine::new();
+        let rest = incoming.extend_sorted(&mut values, |a, b| comparator.partial_compare(a, b));
+
+        // Values equal to a stored one are dropped, like `add` does
+        unsafe {
+            flatten(ptr::addr_of_mut!(self.root));
+            let old = mem::replace(&mut self.root, ptr::null_mut());
+            let vine = merge_vines(old, incoming.into_chain(), |a, b| comparator.compare(a, b));
+            self.root = vine.into_tree();
+        }
+
+        // A value the comparator can't order against itself, like `NaN`, can
+        // still pass the check above when it comes alone. The values from the
+        // first one that can't be linked in order on are added one by one
+        for data in rest.into_iter().chain(values) {
+            self.add(data);
+        }
+    }
+}
+
+impl<T, C> Drop for BinarySearchTree<T, C> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.root);
+        }
+        self.root = ptr::null_mut();
+    }
+}
+
+/// Shape report of a [`BinarySearchTree`], created by
+/// [`BinarySearchTree::shape_stats`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShapeStats {
+    /// Number of nodes
+    pub len: usize,
+    /// Number of levels, 0 when the tree is empty
+    pub height: usize,
+    /// Number of nodes without children
+    pub leaves: usize,
+    /// Number of nodes at each depth, starting with the root's
+    pub depth_histogram: Vec<usize>,
+    /// Average number of nodes visited when searching for a stored value, 0
+    /// when the tree is empty
+    pub average_search_path: f64,
+}
+
+struct Node<T> {
+    data: T,
+    /// Number of nodes in the subtree rooted at this node, itself included
+    size: usize,
+    left: *mut Node<T>,
+    right: *mut Node<T>,
+}
+
+impl<T> Node<T> {
+    fn new(data: T) -> Node<T> {
+        Node {
+            data,
+            size: 1,
+            left: ptr::null_mut(),
+            right: ptr::null_mut(),
+        }
+    }
+
+    fn new_mut(data: T) -> *mut Node<T> {
+        Box::into_raw(Box::new(Self::new(data)))
+    }
+}
+
+impl<T> RawNode for Node<T> {
+    fn children(&mut self) -> (&mut *mut Node<T>, &mut *mut Node<T>) {
+        (&mut self.left, &mut self.right)
+    }
+}
+
+/// Returns `true` if `value` is below the `start` bound of a range, ordered
+/// by `cmp`
+fn is_before<Q, F>(value: &Q, start: Bound<&Q>, cmp: F) -> bool
+where
+    Q: ?Sized,
+    F: Fn(&Q, &Q) -> Ordering,
+{
+    match start {
+        Bound::Included(start) => cmp(value, start) == Ordering::Less,
+        Bound::Excluded(start) => cmp(value, start) != Ordering::Greater,
+        Bound::Unbounded => false,
+    }
+}
+
+/// Returns `true` if `value` is above the `end` bound of a range, ordered by
+/// `cmp`
+fn is_after<Q, F>(value: &Q, end: Bound<&Q>, cmp: F) -> bool
+where
+    Q: ?Sized,
+    F: Fn(&Q, &Q) -> Ordering,
+{
+    match end {
+        Bound::Included(end) => cmp(value, end) == Ordering::Greater,
+        Bound::Excluded(end) => cmp(value, end) != Ordering::Less,
+        Bound::Unbounded => false,
+    }
+}
+
+/// Panics on ranges that can't be walked, like the std collections do
+fn check_bounds<Q, F>(start: Bound<&Q>, end: Bound<&Q>, cmp: F)
+where
+    Q: ?Sized,
+    F: Fn(&Q, &Q) -> Ordering,
+{
+    match (start, end) {
+        (Bound::Excluded(start), Bound::Excluded(end)) if cmp(start, end) == Ordering::Equal => {
+            panic!("range start and end are equal and excluded")
+        }
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) if cmp(start, end) == Ordering::Greater => {
+            panic!("range start is greater than range end")
+        }
+        _ => {}
+    }
+}
+
+/// Size of the subtree rooted at `node`, which may be null
+unsafe fn size<T>(node: *mut Node<T>) -> usize {
+    unsafe { node.as_ref().map_or(0, |node| node.size) }
+}
+
+/// Returns the `k`-th smallest node of the subtree rooted at `node`, counting
+/// from zero, or a null pointer if the subtree is smaller than that
+unsafe fn select<T>(mut node: *mut Node<T>, mut k: usize) -> *mut Node<T> {
+    unsafe {
+        while !node.is_null() {
+            let left = size((*node).left);
+            node = match k.cmp(&left) {
+                Ordering::Less => (*node).left,
+                Ordering::Equal => return node,
+                Ordering::Greater => {
+                    k -= left + 1;
+                    (*node).right
+                }
+            };
+        }
+    }
+    node
+}
+
+/// Walks down from `node` following `cmp`, like [`find`], and counts the
+/// nodes holding values below the searched one
+unsafe fn rank<T, F>(mut node: *mut Node<T>, mut cmp: F) -> usize
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let mut rank = 0;
+
+    unsafe {
+        while !node.is_null() {
+            node = match cmp(&(*node).data) {
+                Ordering::Greater => {
+                    rank += size((*node).left) + 1;
+                    (*node).right
+                }
+                Ordering::Less => (*node).left,
+                Ordering::Equal => return rank + size((*node).left),
+            };
+        }
+    }
+
+    rank
+}
+
+/// Counts the nodes of the subtree rooted at `node` holding values between the
+/// `start` and `end` bounds of a range, ordered by `cmp`. Both ends are ranked
+/// like with [`rank`], so the values in between are never visited
+unsafe fn count_between<T, Q, F>(
+    node: *mut Node<T>,
+    start: Bound<&Q>,
+    end: Bound<&Q>,
+    cmp: F,
+) -> usize
+where
+    Q: ?Sized,
+    F: Fn(&Q, &T) -> Ordering,
+{
+    unsafe {
+        // Ranking with equal values sent right counts them as below the bound
+        let up_to_end = match end {
+            Bound::Included(end) => rank(node, |data| cmp(end, data).then(Ordering::Greater)),
+            Bound::Excluded(end) => rank(node, |data| cmp(end, data)),
+            Bound::Unbounded => size(node),
+        };
+        let before_start = match start {
+            Bound::Included(start) => rank(node, |data| cmp(start, data)),
+            Bound::Excluded(start) => rank(node, |data| cmp(start, data).then(Ordering::Greater)),
+            Bound::Unbounded => 0,
+        };
+        up_to_end.saturating_sub(before_start)
+    }
+}
+
+/// Computes a value for every node of the subtree rooted at `node` from the
+/// values of its children, children first, and returns the value of `node`.
+/// Missing children count as `empty`. Pending nodes are kept in a heap
+/// allocated stack, so degenerate trees can't overflow the call stack
+fn fold_subtrees<T, R, F>(node: *mut Node<T>, empty: R, mut f: F) -> R
+where
+    R: Clone,
+    F: FnMut(&Node<T>, R, R) -> R,
+{
+    let mut pending = vec![(node, false)];
+    let mut results = vec![];
+
+    while let Some((node, children_done)) = pending.pop() {
+        match unsafe { node.as_ref() } {
+            None => results.push(empty.clone()),
+            Some(current) if children_done => {
+                let right = results.pop().unwrap();
+                let left = results.pop().unwrap();
+                results.push(f(current, left, right));
+            }
+            Some(current) => {
+                pending.push((node, true));
+                pending.push((current.right, false));
+                pending.push((current.left, false));
+            }
+        }
+    }
+
+    results.pop().unwrap()
+}
+
+/// Walks down from `node` following `cmp`, which compares the searched value
+/// with each node's data, and returns the matching node or a null pointer.
+/// The search gives up when `cmp` returns `None`, since a value that can't be
+/// ordered against a node has no side of it to be found on
+unsafe fn find<T, F>(mut node: *mut Node<T>, mut cmp: F) -> *mut Node<T>
+where
+    F: FnMut(&T) -> Option<Ordering>,
+{
+    unsafe {
+        while !node.is_null() {
+            node = match cmp(&(*node).data) {
+                Some(Ordering::Greater) => (*node).right,
+                Some(Ordering::Less) => (*node).left,
+                Some(Ordering::Equal) => return node,
+                None => return ptr::null_mut(),
+            };
+        }
+    }
+    node
+}
+
+/// Walks down from `node` following `cmp`, like [`find`], and returns the node
+/// holding the largest value below the searched one, or a null pointer. With
+/// `inclusive`, a node equal to the searched value is returned instead. Gives
+/// up like [`find`] when `cmp` returns `None`
+unsafe fn find_below<T, F>(mut node: *mut Node<T>, mut cmp: F, inclusive: bool) -> *mut Node<T>
+where
+    F: FnMut(&T) -> Option<Ordering>,
+{
+    let mut best = ptr::null_mut();
+
+    unsafe {
+        while !node.is_null() {
+            match cmp(&(*node).data) {
+                Some(Ordering::Equal) if inclusive => return node,
+                Some(Ordering::Greater) => {
+                    best = node;
+                    node = (*node).right;
+                }
+                Some(_) => node = (*node).left,
+                None => return ptr::null_mut(),
+            }
+        }
+    }
+
+    best
+}
+
+/// Walks down from `node` following `cmp`, like [`find`], and returns the node
+/// holding the smallest value above the searched one, or a null pointer. With
+/// `inclusive`, a node equal to the searched value is returned instead. Gives
+/// up like [`find`] when `cmp` returns `None`
+unsafe fn find_above<T, F>(mut node: *mut Node<T>, mut cmp: F, inclusive: bool) -> *mut Node<T>
+where
+    F: FnMut(&T) -> Option<Ordering>,
+{
+    let mut best = ptr::null_mut();
+
+    unsafe {
+        while !node.is_null() {
+            match cmp(&(*node).data) {
+                Some(Ordering::Equal) if inclusive => return node,
+                Some(Ordering::Less) => {
+                    best = node;
+                    node = (*node).left;
+                }
+                Some(_) => node = (*node).right,
+                None => return ptr::null_mut(),
+            }
+        }
+    }
+
+    best
+}
+
+/// Turns taken while walking down from the root, one per level, `true` when
+/// the walk went right. The first 64 are kept inline and only deeper walks
+/// spill to the heap, so recording the way down is free on shallow trees.
+/// Following the turns again reaches the same nodes without comparing values
+struct Turns {
+    inline: u64,
+    spilled: Vec<bool>,
+    len: usize,
+}
+
+impl Turns {
+    fn new() -> Turns {
+        Turns {
+            inline: 0,
+            spilled: Vec::new(),
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, right: bool) {
+        if self.len < u64::BITS as usize {
+            self.inline |= u64::from(right) << self.len;
+        } else {
+            self.spilled.push(right);
+        }
+        self.len += 1;
+    }
+
+    fn went_right(&self, level: usize) -> bool {
+        match level.checked_sub(u64::BITS as usize) {
+            None => (self.inline >> level) & 1 == 1,
+            Some(deeper) => self.spilled[deeper],
+        }
+    }
+
+    /// Follows the turns down from `node`, growing or shrinking by one the
+    /// size of every node passed. Called after a node is linked in, or before
+    /// it is unlinked, at the end of the recorded walk
+    unsafe fn resize<T>(&self, mut node: *mut Node<T>, grow: bool) {
+        unsafe {
+            for level in 0..self.len {
+                if grow {
+                    (*node).size += 1;
+                } else {
+                    (*node).size -= 1;
+                }
+                node = if self.went_right(level) {
+                    (*node).right
+                } else {
+                    (*node).left
+                };
+            }
+        }
+    }
+}
+
+/// Walks down from `link` following `cmp`, like [`find`], and returns the link
+/// that points to the matching node. If there is no match, the returned link
+/// is the empty slot where the searched value belongs. When `cmp` returns
+/// `None` the value has no place in the tree, and a null pointer is returned.
+/// The turns taken above the returned link are recorded, so the sizes along
+/// the way can be fixed once a node is linked in or out without searching
+/// again, and stay consistent even if `cmp` would answer differently on a
+/// second walk
+unsafe fn find_link_turns<T, F>(
+    mut link: *mut *mut Node<T>,
+    turns: &mut Turns,
+    mut cmp: F,
+) -> *mut *mut Node<T>
+where
+    F: FnMut(&T) -> Option<Ordering>,
+{
+    unsafe {
+        while !(*link).is_null() {
+            let node = *link;
+            link = match cmp(&(*node).data) {
+                Some(Ordering::Greater) => ptr::addr_of_mut!((*node).right),
+                Some(Ordering::Less) => ptr::addr_of_mut!((*node).left),
+                Some(Ordering::Equal) => return link,
+                None => return ptr::null_mut(),
+            };
+            turns.push(link == ptr::addr_of_mut!((*node).right));
+        }
+    }
+    link
+}
+
+/// Finds the leftmost node of the subtree `link` points to and unlinks it,
+/// splicing its right child into its place and shrinking the sizes along the
+/// way. The subtree must not be empty
+unsafe fn unlink_min<T>(mut link: *mut *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        while !(**link).left.is_null() {
+            (**link).size -= 1;
+            link = ptr::addr_of_mut!((**link).left);
+        }
+
+        let node = *link;
+        *link = (*node).right;
+        (*node).right = ptr::null_mut();
+        node
+    }
+}
+
+/// Finds the rightmost node of the subtree `link` points to and unlinks it,
+/// splicing its left child into its place and shrinking the sizes along the
+/// way. The subtree must not be empty
+unsafe fn unlink_max<T>(mut link: *mut *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        while !(**link).right.is_null() {
+            (**link).size -= 1;
+            link = ptr::addr_of_mut!((**link).right);
+        }
+
+        let node = *link;
+        *link = (*node).left;
+        (*node).left = ptr::null_mut();
+        node
+    }
+}
+
+/// Leftmost node of the subtree rooted at `node`, or null if it is empty
+unsafe fn leftmost<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        while !node.is_null() && !(*node).left.is_null() {
+            node = (*node).left;
+        }
+    }
+    node
+}
+
+/// Rightmost node of the subtree rooted at `node`, or null if it is empty
+unsafe fn rightmost<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        while !node.is_null() && !(*node).right.is_null() {
+            node = (*node).right;
+        }
+    }
+    node
+}
+
+/// Unlinks the node `link` points to, splicing its children back into the
+/// tree, frees it and returns its data. When the node has two children its
+/// successor is moved into its place, so no data needs to be cloned. The
+/// sizes of the nodes above `link` are left to the caller
+unsafe fn remove_linked<T>(link: *mut *mut Node<T>) -> T {
+    unsafe {
+        let node = *link;
+
+        if (*node).left.is_null() {
+            *link = (*node).right;
+        } else if (*node).right.is_null() {
+            *link = (*node).left;
+        } else {
+            let successor = unlink_min(ptr::addr_of_mut!((*node).right));
+            (*successor).left = (*node).left;
+            (*successor).right = (*node).right;
+            (*successor).size = (*node).size - 1;
+            *link = successor;
+        }
+
+        Box::from_raw(node).data
+    }
+}
+
+/// Chain of nodes linked through their right children, used to build trees
+/// in O(n) without searching for each value. Nodes must be pushed in
+/// ascending order. Nodes still in the vine are freed when it is dropped
+struct Vine<T> {
+    root: *mut Node<T>,
+    last: *mut Node<T>,
+    len: usize,
+}
+
+impl<T> Vine<T> {
+    fn new() -> Vine<T> {
+        Vine {
+            root: ptr::null_mut(),
+            last: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    /// Links `node` after the last node of the vine, dropping its children
+    unsafe fn push(&mut self, node: *mut Node<T>) {
+        unsafe {
+            (*node).left = ptr::null_mut();
+            (*node).right = ptr::null_mut();
+            if self.last.is_null() {
+                self.root = node;
+            } else {
+                (*self.last).right = node;
+            }
+        }
+        self.last = node;
+        self.len += 1;
+    }
+
+    /// Links values from `iter` while they are in ascending order according
+    /// to `cmp`, skipping values equal to the last one. Returns the first
+    /// value that is below the last one, or that `cmp` can't order against
+    /// it, if any. A first value that can't be ordered against itself, like
+    /// NaN with [`NaturalOrder`], is returned as well, since no value after it
+    /// could be ordered against it
+    fn extend_sorted<I, F>(&mut self, iter: &mut I, mut cmp: F) -> Option<T>
+    where
+        I: Iterator<Item = T>,
+        F: FnMut(&T, &T) -> Option<Ordering>,
+    {
+        for data in iter {
+            unsafe {
+                let last = self.last.as_ref().map_or(&data, |last| &last.data);
+                match cmp(&data, last) {
+                    Some(Ordering::Less) | None => return Some(data),
+                    Some(Ordering::Equal) if !self.last.is_null() => continue,
+                    Some(_) => {}
+                }
+                self.push(Node::new_mut(data));
+            }
+        }
+        None
+    }
+
+    /// Links the chain of `len` nodes starting at `chain`, linked through
+    /// their right children in ascending order, after the last node
+    unsafe fn append_chain(&mut self, chain: *mut Node<T>, len: usize) {
+        if chain.is_null() {
+            return;
+        }
+        unsafe {
+            if self.last.is_null() {
+                self.root = chain;
+            } else {
+                (*self.last).right = chain;
+            }
+            self.last = rightmost(chain);
+        }
+        self.len += len;
+    }
+
+    /// Turns the vine into a balanced tree and returns its root
+    fn into_tree(self) -> *mut Node<T> {
+        let len = self.len;
+        let mut root = self.into_chain();
+        unsafe {
+            balance_vine(ptr::addr_of_mut!(root), len);
+        }
+        root
+    }
+
+    /// Hands out the first node of the vine, leaving the chain as is
+    fn into_chain(mut self) -> *mut Node<T> {
+        mem::replace(&mut self.root, ptr::null_mut())
+    }
+}
+
+/// Merges the vines starting at `a` and `b` into a single vine, in ascending
+/// order according to `cmp`. When two nodes are equal the one from `a` is
+/// kept and the one from `b` is freed. Takes ownership of both vines: if `cmp`
+/// panics, every node is still freed
+unsafe fn merge_vines<T, F>(a: *mut Node<T>, b: *mut Node<T>, mut cmp: F) -> Vine<T>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut vine = Vine::new();
+    let (mut a, mut b) = (Tail(a), Tail(b));
+
+    unsafe {
+        loop {
+            let order = match (a.0.as_ref(), b.0.as_ref()) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(a), Some(b)) => cmp(&a.data, &b.data),
+            };
+
+            if order == Ordering::Equal {
+                drop(Box::from_raw(b.pop()));
+            }
+
+            if order == Ordering::Greater {
+                vine.push(b.pop());
+            } else {
+                vine.push(a.pop());
+            }
+        }
+    }
+
+    vine
+}
+
+/// Unmerged part of a vine in [`merge_vines`], freed when dropped so nodes
+/// aren't leaked when a comparison panics
+struct Tail<T>(*mut Node<T>);
+
+impl<T> Tail<T> {
+    /// Detaches the first node of the tail. The tail must not be empty
+    unsafe fn pop(&mut self) -> *mut Node<T> {
+        let node = self.0;
+        self.0 = unsafe { (*node).right };
+        node
+    }
+}
+
+impl<T> Drop for Tail<T> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.0);
+        }
+    }
+}
+
+/// Links `left` and `right` as the children of `pivot`, which must be a
+/// detached node ordered between them, and returns it as the new root
+unsafe fn join_nodes<T>(
+    left: *mut Node<T>,
+    pivot: *mut Node<T>,
+    right: *mut Node<T>,
+) -> *mut Node<T> {
+    unsafe {
+        (*pivot).left = left;
+        (*pivot).right = right;
+        (*pivot).size = size(left) + size(right) + 1;
+    }
+    pivot
+}
+
+impl<T> Drop for Vine<T> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.root);
+        }
+    }
+}
+
+/// Rotates the subtree `link` points to into a vine, a chain of nodes linked
+/// through their right children in ascending order. Sizes are left stale,
+/// [`balance_vine`] sets them again. O(n) time complexity, O(1) space
+/// complexity
+unsafe fn flatten<T>(mut link: *mut *mut Node<T>) {
+    unsafe {
+        while !(*link).is_null() {
+            let node = *link;
+            let left = (*node).left;
+            if left.is_null() {
+                link = ptr::addr_of_mut!((*node).right);
+            } else {
+                (*node).left = (*left).right;
+                (*left).right = node;
+                *link = left;
+            }
+        }
+    }
+}
+
+/// Turns the vine of `len` nodes that `link` points to into a balanced tree
+/// with the Day-Stout-Warren algorithm: repeated passes of left rotations on
+/// every other node of the spine halve its length until the tree is complete.
+/// O(n) time complexity, O(1) space complexity
+unsafe fn balance_vine<T>(link: *mut *mut Node<T>, len: usize) {
+    unsafe {
+        let mut node = *link;
+        let mut size = len;
+        while !node.is_null() {
+            (*node).size = size;
+            size -= 1;
+            node = (*node).right;
+        }
+
+        // Nodes beyond the largest perfect tree become the bottom level
+        let mut perfect = (1 << (len + 1).ilog2()) - 1;
+        compress(link, len - perfect);
+        while perfect > 1 {
+            perfect /= 2;
+            compress(link, perfect);
+        }
+    }
+}
+
+/// Rotates left `count` nodes of the spine starting at `link`, every other
+/// one, keeping their sizes consistent
+unsafe fn compress<T>(mut link: *mut *mut Node<T>, count: usize) {
+    unsafe {
+        for _ in 0..count {
+            let node = *link;
+            let right = (*node).right;
+            (*node).right = (*right).left;
+            (*right).left = node;
+            *link = right;
+
+            (*right).size = (*node).size;
+            (*node).size = size((*node).left) + size((*node).right) + 1;
+            link = ptr::addr_of_mut!((*right).right);
+        }
+    }
+}
+
+/// Takes one step of a Morris in order walk at `node`. Returns the node to
+/// continue from and the node visited by this step, or a null pointer if the
+/// step only threaded a link. Walking until the returned node is null visits
+/// every node in order and removes every thread it added
+unsafe fn morris_step<T>(node: *mut Node<T>) -> (*mut Node<T>, *mut Node<T>) {
+    unsafe {
+        let left = (*node).left;
+        if left.is_null() {
+            return ((*node).right, node);
+        }
+
+        let mut predecessor = left;
+        while !(*predecessor).right.is_null() && (*predecessor).right != node {
+            predecessor = (*predecessor).right;
+        }
+
+        if (*predecessor).right.is_null() {
+            (*predecessor).right = node;
+            (left, ptr::null_mut())
+        } else {
+            (*predecessor).right = ptr::null_mut();
+            ((*node).right, node)
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::get_first)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::live_allocations;
+    use crate::xorshift::XorShift;
+    use std::cell::Cell;
+    use std::collections::BTreeSet;
+    use std::rc::Rc;
+    use std::{panic, thread};
+
+    fn in_order_vec<T: Clone, C: Comparator<T>>(tree: &BinarySearchTree<T, C>) -> Vec<T> {
+        let mut vals = vec![];
+        tree.in_order(&mut |data: &T| vals.push(data.clone()));
+        vals
+    }
+
+    #[test]
+    fn find_root() {
+        let tree = BinarySearchTree::new(10);
+        assert_eq!(tree.get(&10), Some(&10));
+        assert_eq!(tree.get(&9), None);
+    }
+
+    #[test]
+    fn find_node() {
+        let mut tree = BinarySearchTree::new(10);
+        tree.add(5);
+        tree.add(15);
+        assert_eq!(tree.get(&10), Some(&10));
+        assert_eq!(tree.get(&5), Some(&5));
+        assert_eq!(tree.get(&15), Some(&15));
+    }
+
+    #[test]
+    fn find_deleted_root() {
+        let mut tree = BinarySearchTree::new(10);
+
+        tree.add(5);
+        assert_eq!(tree.get(&5), Some(&5));
+
+        tree.delete(&5);
+        assert_eq!(tree.get(&5), None);
+
+        tree.add(15);
+        tree.delete(&15);
+        assert_eq!(tree.get(&15), None);
+
+        tree.add(5);
+        tree.add(1);
+        tree.add(9);
+        tree.delete(&5);
+        assert_eq!(tree.get(&5), None);
+
+        let mut vals: Vec<i32> = vec![];
+        let mut on_find = |&data| vals.push(data);
+        tree.in_order(&mut on_find);
+        assert_eq!(vals.get(0), Some(&1));
+        assert_eq!(vals.get(1), Some(&9));
+        assert_eq!(vals.get(2), Some(&10));
+    }
+
+    #[test]
+    fn find_deleted_node() {
+        let mut tree = BinarySearchTree::new(10);
+
+        tree.add(20);
+        assert_eq!(tree.get(&20), Some(&20));
+
+        tree.delete(&20);
+        assert_eq!(tree.get(&20), None);
+    }
+
+    #[test]
+    fn in_order_check() {
+        let mut vals: Vec<i32> = vec![];
+        let mut tree = BinarySearchTree::new(10);
+        tree.add(5);
+        tree.add(1);
+        tree.add(9);
+        tree.add(15);
+        tree.add(30);
+        tree.add(11);
+
+        let mut func = |&data| vals.push(data);
+        tree.in_order(&mut func);
+        assert_eq!(vals.get(0), Some(&1));
         assert_eq!(vals.get(1), Some(&5));
         assert_eq!(vals.get(2), Some(&9));
         assert_eq!(vals.get(3), Some(&10));
@@ -422,46 +2001,1383 @@ mod tests {
     }
 
     #[test]
-    fn pre_order_check() {
-        let mut vals: Vec<i32> = vec![];
-        let mut tree = BinarySearchTree::new(10);
-        tree.add(5);
-        tree.add(1);
-        tree.add(9);
-        tree.add(15);
-        tree.add(30);
-        tree.add(11);
+    fn pre_order_check() {
+        let mut vals: Vec<i32> = vec![];
+        let mut tree = BinarySearchTree::new(10);
+        tree.add(5);
+        tree.add(1);
+        tree.add(9);
+        tree.add(15);
+        tree.add(30);
+        tree.add(11);
+
+        let mut func = |&data| vals.push(data);
+        tree.pre_order(&mut func);
+        assert_eq!(vals.get(0), Some(&10));
+        assert_eq!(vals.get(1), Some(&5));
+        assert_eq!(vals.get(2), Some(&1));
+        assert_eq!(vals.get(3), Some(&9));
+        assert_eq!(vals.get(4), Some(&15));
+        assert_eq!(vals.get(5), Some(&11));
+        assert_eq!(vals.get(6), Some(&30));
+    }
+
+    #[test]
+    fn post_order_check() {
+        let mut vals: Vec<i32> = vec![];
+        let mut tree = BinarySearchTree::new(10);
+        tree.add(5);
+        tree.add(1);
+        tree.add(9);
+        tree.add(15);
+        tree.add(30);
+        tree.add(11);
+
+        let mut func = |&data| vals.push(data);
+        tree.post_order(&mut func);
+        assert_eq!(vals.get(0), Some(&1));
+        assert_eq!(vals.get(1), Some(&9));
+        assert_eq!(vals.get(2), Some(&5));
+        assert_eq!(vals.get(3), Some(&11));
+        assert_eq!(vals.get(4), Some(&30));
+        assert_eq!(vals.get(5), Some(&15));
+        assert_eq!(vals.get(6), Some(&10));
+    }
+
+    #[test]
+    fn drop_frees_every_node() {
+        let before = live_allocations();
+        {
+            let mut tree = BinarySearchTree::new(String::from("m"));
+            for data in ["f", "t", "a", "h", "p", "z", "b"] {
+                tree.add(String::from(data));
+            }
+            assert!(live_allocations() > before);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn drop_degenerate_tree() {
+        let before = live_allocations();
+        {
+            // Built by hand to skip the search for the insertion point
+            let tree = BinarySearchTree::new(0);
+            let mut left = tree.root;
+            let mut right = tree.root;
+            unsafe {
+                for i in 1..100_000 {
+                    (*left).left = Node::new_mut(-i);
+                    left = (*left).left;
+                    (*right).right = Node::new_mut(i);
+                    right = (*right).right;
+                }
+            }
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn delete_root_leaf() {
+        let mut tree = BinarySearchTree::new(10);
+        tree.delete(&10);
+        assert_eq!(tree.get(&10), None);
+        assert!(in_order_vec(&tree).is_empty());
+
+        // Deleting from an empty tree is a no-op
+        tree.delete(&10);
+        tree.add(5);
+        assert_eq!(in_order_vec(&tree), vec![5]);
+    }
+
+    #[test]
+    fn delete_root_with_one_child() {
+        let mut tree = BinarySearchTree::new(10);
+        tree.add(5);
+        tree.add(1);
+        tree.add(7);
+        tree.delete(&10);
+        assert_eq!(tree.get(&10), None);
+        assert_eq!(in_order_vec(&tree), vec![1, 5, 7]);
+
+        let mut tree = BinarySearchTree::new(10);
+        tree.add(20);
+        tree.add(15);
+        tree.delete(&10);
+        assert_eq!(in_order_vec(&tree), vec![15, 20]);
+    }
+
+    #[test]
+    fn delete_root_with_two_children() {
+        let mut tree = BinarySearchTree::new(10);
+        for data in [5, 20, 15, 30, 17, 1] {
+            tree.add(data);
+        }
+        tree.delete(&10);
+        assert_eq!(tree.get(&10), None);
+        assert_eq!(in_order_vec(&tree), vec![1, 5, 15, 17, 20, 30]);
+    }
+
+    #[test]
+    fn delete_single_child_keeps_subtree() {
+        let mut tree = BinarySearchTree::new(10);
+        for data in [20, 30, 25, 35] {
+            tree.add(data);
+        }
+        tree.delete(&20);
+        assert_eq!(in_order_vec(&tree), vec![10, 25, 30, 35]);
+
+        let mut tree = BinarySearchTree::new(10);
+        for data in [5, 3, 1, 4] {
+            tree.add(data);
+        }
+        tree.delete(&5);
+        assert_eq!(in_order_vec(&tree), vec![1, 3, 4, 10]);
+    }
+
+    #[test]
+    fn delete_frees_node() {
+        let before = live_allocations();
+        {
+            let mut tree = BinarySearchTree::new(String::from("m"));
+            for data in ["f", "t", "a", "h", "p", "z"] {
+                tree.add(String::from(data));
+            }
+            let live = live_allocations();
+            tree.delete(&String::from("m"));
+            tree.delete(&String::from("a"));
+            // Each node owns two allocations: itself and its string
+            assert_eq!(live_allocations(), live - 4);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn delete_matches_btreeset() {
+        for seed in 1..=20u64 {
+            let mut rng = XorShift(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let mut tree = BinarySearchTree::new(50);
+            let mut expected = BTreeSet::from([50]);
+
+            for _ in 0..2_000 {
+                let data = rng.next() % 100;
+                if rng.next().is_multiple_of(3) {
+                    tree.add(data);
+                    expected.insert(data);
+                } else {
+                    tree.delete(&data);
+                    expected.remove(&data);
+                }
+                assert_eq!(tree.get(&data), expected.get(&data));
+            }
+
+            assert_eq!(
+                in_order_vec(&tree),
+                expected.into_iter().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    fn sample_tree() -> BinarySearchTree<i32> {
+        let mut tree = BinarySearchTree::new(10);
+        for data in [5, 1, 9, 15, 30, 11] {
+            tree.add(data);
+        }
+        tree
+    }
+
+    #[test]
+    fn len_tracks_adds_and_deletes() {
+        let mut tree = sample_tree();
+        assert_eq!(tree.len(), 7);
+
+        tree.add(5);
+        assert_eq!(tree.len(), 7);
+
+        tree.delete(&10);
+        tree.delete(&42);
+        assert_eq!(tree.len(), 6);
+
+        for data in [5, 1, 9, 15, 30, 11] {
+            tree.delete(&data);
+        }
+        assert_eq!(tree.len(), 0);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn sizes_survive_inconsistent_comparator() {
+        // Answers reversed every third call, so walking the same value down
+        // twice can take different paths
+        let calls = Cell::new(0u32);
+        let flipping = |a: &u64, b: &u64| {
+            calls.set(calls.get() + 1);
+            if calls.get().is_multiple_of(3) {
+                b.cmp(a)
+            } else {
+                a.cmp(b)
+            }
+        };
+        let mut tree = BinarySearchTree::with_comparator(500, flipping);
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        for step in 0..2000 {
+            let data = rng.next() % 1000;
+            match step % 4 {
+                0 => {
+                    tree.replace(data);
+                }
+                1 => tree.delete(&data),
+                _ => tree.add(data),
+            }
+            assert_eq!(tree.len(), tree.pre_order_iter().count());
+        }
+
+        // Driven from both ends, the walk must still yield every node once
+        let mut seen = BTreeSet::new();
+        let mut iter = IterMut::new(tree.root, tree.len());
+        loop {
+            let data = if seen.len() % 2 == 0 {
+                iter.next()
+            } else {
+                iter.next_back()
+            };
+            match data {
+                Some(data) => assert!(seen.insert(data as *mut u64)),
+                None => break,
+            }
+        }
+        assert_eq!(seen.len(), tree.len());
+    }
+
+    #[test]
+    fn iter_check() {
+        let tree = sample_tree();
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![1, 5, 9, 10, 11, 15, 30]
+        );
+        assert_eq!(
+            tree.iter().rev().copied().collect::<Vec<_>>(),
+            vec![30, 15, 11, 10, 9, 5, 1]
+        );
+        assert_eq!(tree.iter().len(), 7);
+
+        let mut sum = 0;
+        for data in &tree {
+            sum += data;
+        }
+        assert_eq!(sum, 81);
+    }
+
+    #[test]
+    fn iter_both_ends() {
+        let tree = sample_tree();
+        let mut iter = tree.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&30));
+        assert_eq!(iter.next_back(), Some(&15));
+        assert_eq!(iter.next(), Some(&5));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some(&9));
+        assert_eq!(iter.next_back(), Some(&11));
+        assert_eq!(iter.next(), Some(&10));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn iter_stops_early() {
+        let tree = sample_tree();
+        assert_eq!(tree.iter().find(|&&data| data > 9), Some(&10));
+        assert_eq!(tree.iter().skip(2).step_by(2).count(), 3);
+    }
+
+    #[test]
+    fn order_iters_match_callbacks() {
+        let tree = sample_tree();
+
+        let mut vals = vec![];
+        tree.pre_order(&mut |&data| vals.push(data));
+        assert_eq!(tree.pre_order_iter().copied().collect::<Vec<_>>(), vals);
+
+        let mut vals = vec![];
+        tree.post_order(&mut |&data| vals.push(data));
+        assert_eq!(tree.post_order_iter().copied().collect::<Vec<_>>(), vals);
+
+        assert_eq!(
+            tree.level_order_iter().copied().collect::<Vec<_>>(),
+            vec![10, 5, 15, 1, 9, 11, 30]
+        );
+        assert_eq!(tree.pre_order_iter().len(), 7);
+        assert_eq!(tree.post_order_iter().len(), 7);
+        assert_eq!(tree.level_order_iter().len(), 7);
+    }
+
+    #[test]
+    fn get_mut_and_replace() {
+        let mut tree = sample_tree();
+        *tree.get_mut(&9).unwrap() = 8;
+        assert_eq!(tree.get(&8), Some(&8));
+        assert_eq!(tree.get_mut(&7), None);
+
+        let before = live_allocations();
+        {
+            let by_id = ByKey(|record: &(u32, String)| record.0);
+            let mut records = BinarySearchTree::with_comparator((5, "five".to_string()), by_id);
+            assert_eq!(records.replace((2, "two".to_string())), None);
+            assert_eq!(records.len(), 2);
+
+            let old = records.replace((5, "FIVE".to_string()));
+            assert_eq!(old, Some((5, "five".to_string())));
+            assert_eq!(records.len(), 2);
+            assert_eq!(records.get_by_key(&5).unwrap().1, "FIVE");
+
+            records.get_mut_by_key(&2).unwrap().1.push('!');
+            assert_eq!(records.get_by_key(&2).unwrap().1, "two!");
+            assert!(records.get_mut_by_key(&3).is_none());
+            assert!(records.is_valid_bst());
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn try_traversals_stop_early() {
+        let tree = sample_tree();
+
+        let mut visited = vec![];
+        let found = tree.try_in_order(&mut |&data| {
+            visited.push(data);
+            if data > 9 {
+                ControlFlow::Break(data)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(found, ControlFlow::Break(10));
+        assert_eq!(visited, vec![1, 5, 9, 10]);
+
+        let mut visited = vec![];
+        let found = tree.try_pre_order(&mut |&data| {
+            visited.push(data);
+            if data == 9 {
+                ControlFlow::Break("nine")
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(found, ControlFlow::Break("nine"));
+        assert_eq!(visited, vec![10, 5, 1, 9]);
+
+        let mut visited = 0;
+        let found = tree.try_post_order(&mut |_| {
+            visited += 1;
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(found, ControlFlow::Continue(()));
+        assert_eq!(visited, 7);
+    }
+
+    #[test]
+    fn in_order_mut_check() {
+        let by_key = |a: &(i32, i32), b: &(i32, i32)| a.0.cmp(&b.0);
+        let mut tree = BinarySearchTree::with_comparator((2, 0), by_key);
+        tree.add((1, 0));
+        tree.add((3, 0));
+
+        let mut order = vec![];
+        tree.in_order_mut(&mut |pair| {
+            order.push(pair.0);
+            pair.1 = pair.0 * 10;
+        });
+        assert_eq!(order, vec![1, 2, 3]);
+        assert_eq!(tree.get(&(3, 0)), Some(&(3, 30)));
+        assert!(tree.is_valid_bst());
+    }
+
+    #[test]
+    fn level_order_with_depths() {
+        let tree = sample_tree();
+
+        let mut vals = vec![];
+        tree.level_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, vec![10, 5, 15, 1, 9, 11, 30]);
+
+        let depths: Vec<_> = tree
+            .level_order_depth_iter()
+            .map(|(d, &v)| (d, v))
+            .collect();
+        assert_eq!(
+            depths,
+            vec![(0, 10), (1, 5), (1, 15), (2, 1), (2, 9), (2, 11), (2, 30)]
+        );
+        assert_eq!(tree.level_order_depth_iter().len(), 7);
+        assert_eq!(
+            tree.levels(),
+            vec![vec![&10], vec![&5, &15], vec![&1, &9, &11, &30]]
+        );
+
+        // Gaps in a level are skipped, and a spine has one value per level
+        let spine = degenerate_tree(4);
+        assert_eq!(spine.levels(), vec![vec![&0], vec![&1], vec![&2], vec![&3]]);
+        assert_eq!(spine.level_order_depth_iter().last(), Some((3, &3)));
+
+        let empty = BinarySearchTree::<i32>::new_empty();
+        assert!(empty.levels().is_empty());
+        assert_eq!(empty.level_order_depth_iter().next(), None);
+    }
+
+    #[test]
+    fn iters_on_empty_tree() {
+        let tree = BinarySearchTree::<i32>::new_empty();
+        assert_eq!(tree.iter().next(), None);
+        assert_eq!(tree.iter().next_back(), None);
+        assert_eq!(tree.pre_order_iter().next(), None);
+        assert_eq!(tree.post_order_iter().next(), None);
+        assert_eq!(tree.level_order_iter().next(), None);
+        assert_eq!(tree.into_iter().next(), None);
+
+        let mut reversed = BinarySearchTree::<i32, ReverseOrder>::default();
+        assert!(reversed.is_empty());
+        reversed.extend([1, 3, 2]);
+        assert_eq!(in_order_vec(&reversed), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn retain_extract_if_and_drain() {
+        let before = live_allocations();
+        {
+            let mut tree: BinarySearchTree<String> = (0..100).map(|n| format!("{n:03}")).collect();
+
+            tree.retain(|value| !value.ends_with('0'));
+            assert_eq!(tree.len(), 90);
+            assert!(tree.is_valid_bst());
+            assert!(tree.is_balanced());
+
+            let mut visited = 0;
+            let mut extracted = tree.extract_if(|value| {
+                visited += 1;
+                value.ends_with('5')
+            });
+            assert_eq!(extracted.next().as_deref(), Some("005"));
+            assert_eq!(extracted.next().as_deref(), Some("015"));
+            // Values after the last one yielded are kept untested
+            drop(extracted);
+            assert_eq!(visited, 14);
+            assert_eq!(tree.len(), 88);
+            assert!(tree.get(&"025".to_string()).is_some());
+            assert!(tree.is_valid_bst());
+            assert!(tree.is_balanced());
+
+            let drained: Vec<String> = tree.drain().take(2).collect();
+            assert_eq!(drained, vec!["001", "002"]);
+            assert!(tree.is_empty());
+
+            tree.add("x".to_string());
+            tree.retain(|_| false);
+            assert!(tree.is_empty());
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn extract_if_keeps_values_when_pred_panics() {
+        // The panic machinery allocates, so leaks are checked through counts
+        let values: Vec<Rc<u32>> = (1..=20).map(Rc::new).collect();
+        let mut tree = BinarySearchTree::from_sorted(values.iter().cloned());
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            tree.retain(|value| {
+                assert!(**value != 12, "boom");
+                **value % 2 == 0
+            });
+        }));
+        assert!(result.is_err());
+
+        // Odd values before the panic are gone, the rest is untouched
+        let expected: Vec<u32> = (2..=11).step_by(2).chain(12..=20).collect();
+        let kept: Vec<u32> = tree.iter().map(|value| **value).collect();
+        assert_eq!(kept, expected);
+        assert!(tree.is_valid_bst());
+
+        drop(tree);
+        assert!(values.iter().all(|value| Rc::strong_count(value) == 1));
+    }
+
+    #[test]
+    fn into_iter_check() {
+        let before = live_allocations();
+        {
+            let mut tree = BinarySearchTree::new(String::from("m"));
+            for data in ["f", "t", "a", "h", "p", "z"] {
+                tree.add(String::from(data));
+            }
+            let vals: Vec<String> = tree.into_iter().collect();
+            assert_eq!(vals, vec!["a", "f", "h", "m", "p", "t", "z"]);
+        }
+        assert_eq!(live_allocations(), before);
+
+        {
+            let mut tree = BinarySearchTree::new(String::from("m"));
+            for data in ["f", "t", "a", "h", "p", "z"] {
+                tree.add(String::from(data));
+            }
+            let mut iter = tree.into_iter();
+            assert_eq!(iter.next().as_deref(), Some("a"));
+            assert_eq!(iter.len(), 6);
+            // Dropping the iterator frees the nodes that were not yielded
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn iters_match_btreeset() {
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+        let mut tree = BinarySearchTree::new(500);
+        let mut expected = BTreeSet::from([500]);
+        for _ in 0..1_000 {
+            let data = rng.next() % 1_000;
+            tree.add(data);
+            expected.insert(data);
+        }
+
+        assert!(tree.iter().eq(expected.iter()));
+        assert!(tree.iter().rev().eq(expected.iter().rev()));
+        assert_eq!(tree.pre_order_iter().count(), expected.len());
+        assert_eq!(tree.post_order_iter().last(), Some(&500));
+        assert!(tree.into_iter().eq(expected.into_iter()));
+    }
+
+    #[test]
+    fn range_check() {
+        let tree = sample_tree();
+        assert_eq!(tree.range(5..11).collect::<Vec<_>>(), vec![&5, &9, &10]);
+        assert_eq!(tree.range(6..=11).collect::<Vec<_>>(), vec![&9, &10, &11]);
+        assert_eq!(tree.range(..5).collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(tree.range(12..).collect::<Vec<_>>(), vec![&15, &30]);
+        assert_eq!(tree.range(..).count(), 7);
+        assert_eq!(tree.range(2..5).next(), None);
+        assert_eq!(tree.range(31..).next_back(), None);
+        assert_eq!(tree.range(10..10).next(), None);
+        assert_eq!(
+            tree.range((Bound::Excluded(5), Bound::Excluded(15)))
+                .rev()
+                .collect::<Vec<_>>(),
+            vec![&11, &10, &9]
+        );
+        assert_eq!(tree.count_range(9..=15), 4);
+        assert_eq!(tree.count_range(..), 7);
+        assert_eq!(tree.count_range(2..5), 0);
+        assert_eq!(
+            tree.count_range((Bound::Excluded(5), Bound::Excluded(15))),
+            3
+        );
+    }
+
+    #[test]
+    fn range_both_ends() {
+        let tree = sample_tree();
+        let mut range = tree.range(1..=15);
+        assert_eq!(range.next(), Some(&1));
+        assert_eq!(range.next_back(), Some(&15));
+        assert_eq!(range.next_back(), Some(&11));
+        assert_eq!(range.next(), Some(&5));
+        assert_eq!(range.next(), Some(&9));
+        assert_eq!(range.next_back(), Some(&10));
+        assert_eq!(range.next(), None);
+        assert_eq!(range.next_back(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "range start is greater than range end")]
+    fn range_reversed_bounds() {
+        sample_tree().range((Bound::Included(9), Bound::Excluded(5)));
+    }
 
-        let mut func = |&data| vals.push(data);
-        tree.pre_order(&mut func);
-        assert_eq!(vals.get(0), Some(&10));
-        assert_eq!(vals.get(1), Some(&5));
-        assert_eq!(vals.get(2), Some(&1));
-        assert_eq!(vals.get(3), Some(&9));
-        assert_eq!(vals.get(4), Some(&15));
-        assert_eq!(vals.get(5), Some(&11));
-        assert_eq!(vals.get(6), Some(&30));
+    #[test]
+    fn range_matches_btreeset() {
+        let mut rng = XorShift(0x5DEE_CE66_D1CE_4E5B);
+        let mut tree = BinarySearchTree::new(100);
+        let mut expected = BTreeSet::from([100]);
+        for _ in 0..300 {
+            let data = rng.next() % 200;
+            tree.add(data);
+            expected.insert(data);
+        }
+
+        for _ in 0..500 {
+            let a = rng.next() % 210;
+            let b = rng.next() % 210;
+            let (start, end) = (a.min(b), a.max(b));
+            assert!(tree.range(start..end).eq(expected.range(start..end)));
+            assert_eq!(
+                tree.count_range(start..=end),
+                expected.range(start..=end).count()
+            );
+            assert_eq!(tree.count_range(..end), expected.range(..end).count());
+            assert!(tree
+                .range(start..=end)
+                .rev()
+                .eq(expected.range(start..=end).rev()));
+
+            // Alternate ends until they meet
+            let mut range = tree.range(start..=end);
+            let mut expected_range = expected.range(start..=end);
+            loop {
+                let (got, want) = if rng.next().is_multiple_of(2) {
+                    (range.next(), expected_range.next())
+                } else {
+                    (range.next_back(), expected_range.next_back())
+                };
+                assert_eq!(got, want);
+                if got.is_none() {
+                    break;
+                }
+            }
+        }
     }
 
     #[test]
-    fn post_order_check() {
-        let mut vals: Vec<i32> = vec![];
-        let mut tree = BinarySearchTree::new(10);
-        tree.add(5);
-        tree.add(1);
-        tree.add(9);
-        tree.add(15);
-        tree.add(30);
-        tree.add(11);
+    fn floor_and_ceiling() {
+        let tree = sample_tree();
+        assert_eq!(tree.floor(&10), Some(&10));
+        assert_eq!(tree.floor(&14), Some(&11));
+        assert_eq!(tree.floor(&100), Some(&30));
+        assert_eq!(tree.floor(&0), None);
 
-        let mut func = |&data| vals.push(data);
-        tree.post_order(&mut func);
-        assert_eq!(vals.get(0), Some(&1));
-        assert_eq!(vals.get(1), Some(&9));
-        assert_eq!(vals.get(2), Some(&5));
-        assert_eq!(vals.get(3), Some(&11));
-        assert_eq!(vals.get(4), Some(&30));
-        assert_eq!(vals.get(5), Some(&15));
-        assert_eq!(vals.get(6), Some(&10));
+        assert_eq!(tree.ceiling(&10), Some(&10));
+        assert_eq!(tree.ceiling(&12), Some(&15));
+        assert_eq!(tree.ceiling(&-5), Some(&1));
+        assert_eq!(tree.ceiling(&31), None);
+    }
+
+    #[test]
+    fn predecessor_and_successor() {
+        let tree = sample_tree();
+        assert_eq!(tree.predecessor(&10), Some(&9));
+        assert_eq!(tree.predecessor(&12), Some(&11));
+        assert_eq!(tree.predecessor(&1), None);
+
+        assert_eq!(tree.successor(&10), Some(&11));
+        assert_eq!(tree.successor(&9), Some(&10));
+        assert_eq!(tree.successor(&0), Some(&1));
+        assert_eq!(tree.successor(&30), None);
+    }
+
+    #[test]
+    fn neighbors_match_btreeset() {
+        let mut rng = XorShift(0x1234_5678_9ABC_DEF1);
+        let mut tree = BinarySearchTree::new(250);
+        let mut expected = BTreeSet::from([250]);
+        for _ in 0..200 {
+            let data = rng.next() % 500;
+            tree.add(data);
+            expected.insert(data);
+        }
+
+        for data in 0..510 {
+            assert_eq!(tree.floor(&data), expected.range(..=data).next_back());
+            assert_eq!(tree.ceiling(&data), expected.range(data..).next());
+            assert_eq!(tree.predecessor(&data), expected.range(..data).next_back());
+            assert_eq!(tree.successor(&data), expected.range(data + 1..).next());
+        }
+    }
+
+    #[test]
+    fn min_and_max() {
+        let mut tree = sample_tree();
+        assert_eq!(tree.min(), Some(&1));
+        assert_eq!(tree.max(), Some(&30));
+
+        tree.delete(&1);
+        tree.delete(&30);
+        assert_eq!(tree.min(), Some(&5));
+        assert_eq!(tree.max(), Some(&15));
+
+        let tree = BinarySearchTree::<i32>::new_empty();
+        assert_eq!(tree.min(), None);
+        assert_eq!(tree.max(), None);
+    }
+
+    #[test]
+    fn pop_min_and_max() {
+        let mut tree = sample_tree();
+        assert_eq!(tree.pop_min(), Some(1));
+        assert_eq!(tree.pop_max(), Some(30));
+        assert_eq!(tree.pop_min(), Some(5));
+        assert_eq!(tree.len(), 4);
+        assert_eq!(in_order_vec(&tree), vec![9, 10, 11, 15]);
+
+        // Pops the root once everything below it is gone
+        assert_eq!(tree.pop_max(), Some(15));
+        assert_eq!(tree.pop_max(), Some(11));
+        assert_eq!(tree.pop_max(), Some(10));
+        assert_eq!(tree.pop_max(), Some(9));
+        assert_eq!(tree.pop_max(), None);
+        assert_eq!(tree.pop_min(), None);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn pop_min_drains_in_order() {
+        let before = live_allocations();
+        {
+            let mut rng = XorShift(0xDEAD_BEEF_CAFE_F00D);
+            let mut tree = BinarySearchTree::new(500);
+            let mut expected = BTreeSet::from([500]);
+            for _ in 0..500 {
+                let data = rng.next() % 1_000;
+                tree.add(data);
+                expected.insert(data);
+            }
+
+            while let Some(data) = tree.pop_min() {
+                assert_eq!(Some(data), expected.pop_first());
+                if let Some(data) = tree.pop_max() {
+                    assert_eq!(Some(data), expected.pop_last());
+                }
+            }
+            assert!(expected.is_empty());
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    /// Checks every node's subtree size against a recount, returning the
+    /// size of the subtree rooted at `node`
+    fn check_sizes<T>(node: *mut Node<T>) -> usize {
+        if node.is_null() {
+            return 0;
+        }
+        unsafe {
+            let size = check_sizes((*node).left) + check_sizes((*node).right) + 1;
+            assert_eq!((*node).size, size);
+            size
+        }
+    }
+
+    #[test]
+    fn select_and_rank() {
+        let tree = sample_tree();
+        let values = in_order_vec(&tree);
+        for (k, data) in values.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(data));
+            assert_eq!(tree.rank(data), k);
+        }
+        assert_eq!(tree.select(values.len()), None);
+
+        // Absent values are ranked by where they would be inserted
+        assert_eq!(tree.rank(&0), 0);
+        assert_eq!(tree.rank(&12), 5);
+        assert_eq!(tree.rank(&31), 7);
+    }
+
+    #[test]
+    fn sizes_track_mutations() {
+        let mut tree = sample_tree();
+        assert_eq!(check_sizes(tree.root), 7);
+
+        tree.delete(&10);
+        assert_eq!(check_sizes(tree.root), 6);
+        tree.delete(&9);
+        tree.add(12);
+        assert_eq!(check_sizes(tree.root), 6);
+        tree.pop_min();
+        tree.pop_max();
+        assert_eq!(check_sizes(tree.root), 4);
+        assert_eq!(tree.len(), 4);
+
+        // Duplicates and missing values leave the sizes alone
+        tree.add(12);
+        tree.delete(&100);
+        assert_eq!(check_sizes(tree.root), 4);
+    }
+
+    #[test]
+    fn select_and_rank_match_btreeset() {
+        for seed in 1..=10u64 {
+            let mut rng = XorShift(seed.wrapping_mul(0xD1B5_4A32_D192_ED03));
+            let mut tree = BinarySearchTree::new(500);
+            let mut expected = BTreeSet::from([500]);
+
+            for _ in 0..2_000 {
+                let data = rng.next() % 1_000;
+                match rng.next() % 6 {
+                    0 => assert_eq!(tree.pop_min(), expected.pop_first()),
+                    1 => assert_eq!(tree.pop_max(), expected.pop_last()),
+                    2 => {
+                        tree.delete(&data);
+                        expected.remove(&data);
+                    }
+                    _ => {
+                        tree.add(data);
+                        expected.insert(data);
+                    }
+                }
+
+                assert_eq!(tree.len(), expected.len());
+                assert_eq!(tree.rank(&data), expected.range(..data).count());
+                let k = (data as usize) % (expected.len() + 1);
+                assert_eq!(tree.select(k), expected.iter().nth(k));
+            }
+
+            assert_eq!(check_sizes(tree.root), expected.len());
+        }
+    }
+
+    #[test]
+    fn from_sorted_is_balanced() {
+        for len in 0..=64 {
+            let tree = BinarySearchTree::from_sorted(0..len);
+            assert_eq!(in_order_vec(&tree), (0..len).collect::<Vec<_>>());
+            assert_eq!(check_sizes(tree.root), len as usize);
+            let expected = (len as usize + 1).next_power_of_two().ilog2();
+            assert_eq!(tree.height(), expected as usize);
+        }
+    }
+
+    #[test]
+    fn from_sorted_skips_duplicates() {
+        let tree = BinarySearchTree::from_sorted([1, 1, 2, 3, 3, 3, 4]);
+        assert_eq!(in_order_vec(&tree), vec![1, 2, 3, 4]);
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "not in ascending order")]
+    fn from_sorted_rejects_unsorted() {
+        BinarySearchTree::from_sorted([1, 3, 2]);
+    }
+
+    #[test]
+    fn from_iter_check() {
+        let before = live_allocations();
+        {
+            let tree: BinarySearchTree<i32> = (0..1_000).collect();
+            assert_eq!(tree.height(), 10);
+            assert_eq!(check_sizes(tree.root), 1_000);
+
+            // The sorted prefix is balanced, the rest is added one by one
+            let tree: BinarySearchTree<i32> = [1, 2, 3, 4, 5, 0, 3, 7].into_iter().collect();
+            assert_eq!(in_order_vec(&tree), vec![0, 1, 2, 3, 4, 5, 7]);
+            assert_eq!(check_sizes(tree.root), 7);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn extend_check() {
+        let before = live_allocations();
+        {
+            let mut tree = BinarySearchTree::from_sorted([2, 4, 6]);
+
+            // Sorted and large enough: merged into a balanced tree
+            tree.extend([1, 2, 3, 3, 5, 7, 9, 11]);
+            assert_eq!(in_order_vec(&tree), vec![1, 2, 3, 4, 5, 6, 7, 9, 11]);
+            assert_eq!(check_sizes(tree.root), 9);
+            assert_eq!(tree.height(), 4);
+
+            // Unsorted or small batches are added one by one
+            tree.extend([10, 8]);
+            tree.extend([0]);
+            assert_eq!(
+                in_order_vec(&tree),
+                vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]
+            );
+            assert_eq!(check_sizes(tree.root), 12);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn extend_keeps_values_after_nan() {
+        // NaN can't be ordered against its neighbours, so the batch isn't
+        // taken as sorted
+        let mut tree = BinarySearchTree::new(0.25);
+        tree.extend([1.0, f64::NAN, 0.5, 2.0]);
+        assert_eq!(tree.len(), 4);
+        assert_eq!(in_order_vec(&tree), vec![0.25, 0.5, 1.0, 2.0]);
+        assert_eq!(check_sizes(tree.root), 4);
+        assert!(tree.is_valid_bst());
+    }
+
+    #[test]
+    fn extend_keeps_values_after_leading_nan() {
+        let mut tree = BinarySearchTree::new(5.0);
+        tree.extend([f64::NAN, 1.0, 2.0]);
+        assert_eq!(in_order_vec(&tree), vec![1.0, 2.0, 5.0]);
+        assert_eq!(check_sizes(tree.root), 3);
+
+        // A lone NaN passes the sortedness check, and is then added like
+        // `add` would
+        let mut tree = BinarySearchTree::<f64>::new_empty();
+        tree.extend([f64::NAN]);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(check_sizes(tree.root), 1);
+
+        // Collecting adds the values from the NaN on one by one
+        let tree: BinarySearchTree<f64> = [1.0, f64::NAN, 2.0].into_iter().collect();
+        assert_eq!(in_order_vec(&tree), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not in ascending order")]
+    fn from_sorted_rejects_nan() {
+        BinarySearchTree::from_sorted([f64::NAN, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn extend_matches_btreeset() {
+        let mut rng = XorShift(0x5851_F42D_4C95_7F2D);
+        let mut tree = BinarySearchTree::new(500);
+        let mut expected = BTreeSet::from([500]);
+
+        for round in 0..50 {
+            let mut values: Vec<u64> = (0..round * 4).map(|_| rng.next() % 1_000).collect();
+            if round % 2 == 0 {
+                values.sort();
+            }
+            tree.extend(values.iter().copied());
+            expected.extend(values);
+
+            assert_eq!(check_sizes(tree.root), expected.len());
+            assert!(tree.iter().eq(expected.iter()));
+        }
+    }
+
+    #[test]
+    fn rebalance_check() {
+        let before = live_allocations();
+        {
+            let mut tree = BinarySearchTree::new(0);
+            for data in 1..2_000 {
+                tree.add(data);
+            }
+            assert_eq!(tree.height(), 2_000);
+
+            tree.rebalance();
+            assert_eq!(tree.height(), 11);
+            assert_eq!(check_sizes(tree.root), 2_000);
+            assert!(tree.iter().copied().eq(0..2_000));
+
+            tree.delete(&0);
+            tree.add(5_000);
+            assert_eq!(check_sizes(tree.root), 2_000);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn set_algebra_check() {
+        let a = sample_tree();
+        let b = BinarySearchTree::from_sorted([0, 5, 10, 12, 30, 40]);
+
+        let union = [0, 1, 5, 9, 10, 11, 12, 15, 30, 40];
+        assert!(a.union(&b).copied().eq(union));
+        assert!(a.intersection(&b).copied().eq([5, 10, 30]));
+        assert!(a.difference(&b).copied().eq([1, 9, 11, 15]));
+        assert!(b.difference(&a).copied().eq([0, 12, 40]));
+        assert!(a
+            .symmetric_difference(&b)
+            .copied()
+            .eq([0, 1, 9, 11, 12, 15, 40]));
+    }
+
+    #[test]
+    fn set_algebra_matches_btreeset() {
+        let mut rng = XorShift(0x94D0_49BB_1331_11EB);
+        for _ in 0..50 {
+            let a_values: BTreeSet<u64> = (0..rng.next() % 100).map(|_| rng.next() % 150).collect();
+            let b_values: BTreeSet<u64> = (0..rng.next() % 100).map(|_| rng.next() % 150).collect();
+            let a: BinarySearchTree<u64> = a_values.iter().copied().collect();
+            let b: BinarySearchTree<u64> = b_values.iter().copied().collect();
+
+            assert!(a.union(&b).eq(a_values.union(&b_values)));
+            assert!(a.intersection(&b).eq(a_values.intersection(&b_values)));
+            assert!(a.difference(&b).eq(a_values.difference(&b_values)));
+            assert!(a
+                .symmetric_difference(&b)
+                .eq(a_values.symmetric_difference(&b_values)));
+            assert_eq!(a.is_subset(&b), a_values.is_subset(&b_values));
+            assert_eq!(a.is_superset(&b), a_values.is_superset(&b_values));
+            assert_eq!(a.is_disjoint(&b), a_values.is_disjoint(&b_values));
+        }
+    }
+
+    #[test]
+    fn subset_and_disjoint() {
+        let a = BinarySearchTree::from_sorted([2, 4]);
+        let b = BinarySearchTree::from_sorted([1, 2, 3, 4]);
+        let c = BinarySearchTree::from_sorted([5, 6]);
+
+        assert!(a.is_subset(&b));
+        assert!(!b.is_subset(&a));
+        assert!(b.is_superset(&a));
+        assert!(a.is_subset(&a));
+        assert!(a.is_disjoint(&c));
+        assert!(!a.is_disjoint(&b));
+
+        let empty = BinarySearchTree::from_sorted(Vec::<i32>::new());
+        assert!(empty.is_subset(&a));
+        assert!(empty.is_disjoint(&a));
+        assert_eq!(a.union(&empty).count(), 2);
+    }
+
+    #[test]
+    fn split_off_check() {
+        let mut low = sample_tree();
+        let high = low.split_off(&10);
+        assert_eq!(in_order_vec(&low), vec![1, 5, 9]);
+        assert_eq!(in_order_vec(&high), vec![10, 11, 15, 30]);
+        assert_eq!(check_sizes(low.root), 3);
+        assert_eq!(check_sizes(high.root), 4);
+
+        // Splitting past either end moves everything or nothing
+        let everything = low.split_off(&0);
+        assert!(low.is_empty());
+        assert_eq!(everything.len(), 3);
+        let mut high = high;
+        assert!(high.split_off(&31).is_empty());
+        assert_eq!(high.len(), 4);
+    }
+
+    #[test]
+    fn split_off_matches_btreeset() {
+        let before = live_allocations();
+        {
+            let mut rng = XorShift(0xBF58_476D_1CE4_E5B9);
+            for _ in 0..50 {
+                let mut expected: BTreeSet<u64> = (0..200).map(|_| rng.next() % 300).collect();
+                let mut tree: BinarySearchTree<u64> = expected.iter().rev().copied().collect();
+                let key = rng.next() % 300;
+
+                let high = tree.split_off(&key);
+                let expected_high = expected.split_off(&key);
+                assert!(tree.iter().eq(expected.iter()));
+                assert!(high.iter().eq(expected_high.iter()));
+                assert_eq!(check_sizes(tree.root), expected.len());
+                assert_eq!(check_sizes(high.root), expected_high.len());
+            }
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn append_check() {
+        let before = live_allocations();
+        {
+            let mut tree = BinarySearchTree::from_sorted([1, 2, 3]);
+
+            // Ranges that don't overlap are joined in both directions
+            let mut other = BinarySearchTree::from_sorted([7, 8, 9]);
+            tree.append(&mut other);
+            let mut other = BinarySearchTree::from_sorted([-2, -1]);
+            tree.append(&mut other);
+            assert!(other.is_empty());
+            assert_eq!(in_order_vec(&tree), vec![-2, -1, 1, 2, 3, 7, 8, 9]);
+            assert_eq!(check_sizes(tree.root), 8);
+
+            // Overlapping ranges are merged, without duplicates
+            let mut other = BinarySearchTree::from_sorted([0, 2, 4, 6, 8, 10]);
+            tree.append(&mut other);
+            assert!(other.is_empty());
+            assert_eq!(
+                in_order_vec(&tree),
+                vec![-2, -1, 0, 1, 2, 3, 4, 6, 7, 8, 9, 10]
+            );
+            assert_eq!(check_sizes(tree.root), 12);
+
+            // Appending to and from empty trees
+            let mut empty = BinarySearchTree::from_sorted(Vec::new());
+            tree.append(&mut empty);
+            empty.append(&mut tree);
+            assert!(tree.is_empty());
+            assert_eq!(empty.len(), 12);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn split_off_then_append_round_trips() {
+        let mut rng = XorShift(0x9FB2_1C65_1E98_DF25);
+        let mut tree: BinarySearchTree<u64> = (0..500).map(|_| rng.next() % 1_000).collect();
+        let expected = in_order_vec(&tree);
+
+        for _ in 0..20 {
+            let mut high = tree.split_off(&(rng.next() % 1_000));
+            tree.append(&mut high);
+            assert_eq!(in_order_vec(&tree), expected);
+            assert_eq!(check_sizes(tree.root), expected.len());
+        }
+    }
+
+    #[test]
+    fn join_check() {
+        let left = BinarySearchTree::from_sorted([1, 2, 3]);
+        let right = BinarySearchTree::from_sorted([5, 6]);
+        let tree = BinarySearchTree::join(left, 4, right);
+        assert_eq!(in_order_vec(&tree), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(check_sizes(tree.root), 6);
+
+        let empty = || BinarySearchTree::from_sorted(Vec::new());
+        let tree = BinarySearchTree::join(empty(), 1, empty());
+        assert_eq!(in_order_vec(&tree), vec![1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "join needs every value")]
+    fn join_rejects_overlap() {
+        let left = BinarySearchTree::from_sorted([1, 2, 3]);
+        let right = BinarySearchTree::from_sorted([5, 6]);
+        BinarySearchTree::join(left, 3, right);
+    }
+
+    #[test]
+    fn reverse_order_tree() {
+        let mut tree: BinarySearchTree<i32, ReverseOrder> = [1, 5, 3, 9, 7].into_iter().collect();
+        assert_eq!(in_order_vec(&tree), vec![9, 7, 5, 3, 1]);
+        assert_eq!(tree.min(), Some(&9));
+        assert_eq!(tree.select(1), Some(&7));
+        assert_eq!(tree.rank(&4), 3);
+        assert_eq!(tree.floor(&4), Some(&5));
+        let bounds = (Bound::Included(7), Bound::Included(3));
+        assert!(tree.range(bounds).copied().eq([7, 5, 3]));
+
+        let low = tree.split_off(&5);
+        assert_eq!(in_order_vec(&tree), vec![9, 7]);
+        assert_eq!(in_order_vec(&low), vec![5, 3, 1]);
+    }
+
+    #[test]
+    fn closure_comparator_tree() {
+        let case_insensitive = |a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase());
+        let mut tree = BinarySearchTree::with_comparator(String::from("banana"), case_insensitive);
+        for word in ["Apple", "cherry", "BANANA", "apple"] {
+            tree.add(String::from(word));
+        }
+
+        assert_eq!(tree.len(), 3);
+        assert_eq!(in_order_vec(&tree), vec!["Apple", "banana", "cherry"]);
+        let cherry = String::from("cherry");
+        assert_eq!(tree.get(&String::from("CHERRY")), Some(&cherry));
+
+        tree.delete(&String::from("APPLE"));
+        assert_eq!(in_order_vec(&tree), vec!["banana", "cherry"]);
+    }
+
+    #[test]
+    fn nan_handling() {
+        // Incomparable values have no place in the natural order: they are
+        // never added, and never found
+        let mut tree = BinarySearchTree::new(1.0);
+        tree.add(2.0);
+        tree.add(0.5);
+        tree.add(f64::NAN);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&f64::NAN), None);
+        assert_eq!(tree.get_mut(&f64::NAN), None);
+        assert_eq!(tree.floor(&f64::NAN), None);
+        assert_eq!(tree.ceiling(&f64::NAN), None);
+        assert_eq!(tree.predecessor(&f64::NAN), None);
+        assert_eq!(tree.successor(&f64::NAN), None);
+        assert_eq!(tree.replace(f64::NAN), None);
+        assert_eq!(in_order_vec(&tree), vec![0.5, 1.0, 2.0]);
+
+        // Deleting NaN leaves every value, the root included, in place
+        tree.delete(&f64::NAN);
+        assert_eq!(in_order_vec(&tree), vec![0.5, 1.0, 2.0]);
+        assert!(tree.is_valid_bst());
+
+        let mut tree = BinarySearchTree::with_comparator(1.0, TotalOrder);
+        for data in [f64::NAN, 3.0, f64::NAN, -2.0, f64::NAN] {
+            tree.add(data);
+        }
+        assert_eq!(tree.len(), 4);
+        assert!(tree.get(&f64::NAN).unwrap().is_nan());
+        assert_eq!(tree.rank(&f64::NAN), 3);
+        assert_eq!(tree.range(..f64::NAN).count(), 3);
+
+        tree.delete(&f64::NAN);
+        assert_eq!(in_order_vec(&tree), vec![-2.0, 1.0, 3.0]);
+    }
+
+    #[test]
+    fn height_and_balance() {
+        let mut tree = sample_tree();
+        assert_eq!(tree.height(), 3);
+        assert!(tree.is_balanced());
+
+        tree.add(31);
+        tree.add(32);
+        assert_eq!(tree.height(), 5);
+        assert!(!tree.is_balanced());
+
+        let empty = BinarySearchTree::from_sorted(Vec::<i32>::new());
+        assert_eq!(empty.height(), 0);
+        assert!(empty.is_balanced());
+    }
+
+    #[test]
+    fn valid_bst_check() {
+        let tree = sample_tree();
+        assert!(tree.is_valid_bst());
+        assert!(BinarySearchTree::from_sorted(Vec::<i32>::new()).is_valid_bst());
+
+        // Swapping two values breaks the ordering
+        let tree = sample_tree();
+        unsafe {
+            let left = (*tree.root).left;
+            mem::swap(&mut (*tree.root).data, &mut (*left).data);
+        }
+        assert!(!tree.is_valid_bst());
+
+        // A stale size breaks the augmentation
+        let tree = sample_tree();
+        unsafe {
+            (*(*tree.root).right).size += 1;
+        }
+        assert!(!tree.is_valid_bst());
+    }
+
+    #[test]
+    fn shape_stats_check() {
+        let stats = sample_tree().shape_stats();
+        assert_eq!(
+            stats,
+            ShapeStats {
+                len: 7,
+                height: 3,
+                leaves: 4,
+                depth_histogram: vec![1, 2, 4],
+                average_search_path: 17.0 / 7.0,
+            }
+        );
+
+        let stats = BinarySearchTree::from_sorted(Vec::<i32>::new()).shape_stats();
+        assert_eq!(stats.height, 0);
+        assert_eq!(stats.average_search_path, 0.0);
+        assert!(stats.depth_histogram.is_empty());
+    }
+
+    #[test]
+    fn diagnostics_on_degenerate_tree() {
+        let mut tree = BinarySearchTree::new(0);
+        for data in 1..3_000 {
+            tree.add(data);
+        }
+
+        assert_eq!(tree.height(), 3_000);
+        assert!(!tree.is_balanced());
+        assert!(tree.is_valid_bst());
+
+        let stats = tree.shape_stats();
+        assert_eq!(stats.leaves, 1);
+        assert_eq!(stats.depth_histogram, vec![1; 3_000]);
+        assert_eq!(stats.average_search_path, 1_500.5);
+    }
+
+    /// Links `0..len` into a single right spine, with the sizes set, without
+    /// going through `add`
+    fn degenerate_tree(len: u64) -> BinarySearchTree<u64> {
+        let mut root = ptr::null_mut();
+        for (size, data) in (0..len).rev().enumerate() {
+            let node = Node::new_mut(data);
+            unsafe {
+                (*node).right = root;
+                (*node).size = size + 1;
+            }
+            root = node;
+        }
+        BinarySearchTree {
+            root,
+            comparator: NaturalOrder,
+        }
+    }
+
+    #[test]
+    fn million_node_degenerate_tree() {
+        // A small stack makes any recursion proportional to the height fail
+        let worker = thread::Builder::new().stack_size(128 * 1024).spawn(|| {
+            let mut tree = degenerate_tree(1_000_000);
+            assert_eq!(tree.height(), 1_000_000);
+            assert!(tree.is_valid_bst());
+            assert!(!tree.is_balanced());
+            assert_eq!(tree.shape_stats().leaves, 1);
+
+            tree.add(1_000_000);
+            tree.delete(&500_000);
+            assert_eq!(tree.get(&999_999), Some(&999_999));
+            assert_eq!(tree.get(&500_000), None);
+            assert_eq!(tree.select(999_999), Some(&1_000_000));
+            assert_eq!(tree.rank(&750_000), 749_999);
+            assert_eq!(tree.floor(&500_000), Some(&499_999));
+
+            let mut sum = 0;
+            tree.in_order(&mut |&data| sum += data);
+            tree.pre_order(&mut |&data| sum += data);
+            tree.post_order(&mut |&data| sum += data);
+            tree.in_order_morris(&mut |&data| sum += data);
+            let expected = 1_000_000 * 1_000_001 / 2 - 500_000;
+            assert_eq!(sum, 4 * expected);
+            assert_eq!(tree.iter().rev().count(), 1_000_000);
+            assert_eq!(tree.level_order_iter().count(), 1_000_000);
+            assert_eq!(tree.range(10..20).count(), 10);
+
+            assert_eq!(tree.pop_min(), Some(0));
+            assert_eq!(tree.pop_max(), Some(1_000_000));
+
+            let high = tree.split_off(&750_000);
+            assert_eq!(high.into_iter().count(), 250_000);
+            tree.rebalance();
+            assert_eq!(tree.height(), 20);
+            assert!(tree.is_valid_bst());
+        });
+        worker.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn in_order_morris_restores_links() {
+        let mut tree = sample_tree();
+        let mut values = vec![];
+        tree.in_order_morris(&mut |&data| values.push(data));
+        assert_eq!(values, in_order_vec(&tree));
+        assert!(tree.is_valid_bst());
+
+        // A panicking callback still leaves the tree untouched
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            tree.in_order_morris(&mut |&data| {
+                if data == 10 {
+                    panic!("stop");
+                }
+            });
+        }));
+        assert!(result.is_err());
+        assert!(tree.is_valid_bst());
+        assert_eq!(in_order_vec(&tree), values);
+    }
+
+    #[test]
+    fn append_survives_panicking_comparator() {
+        // The panic machinery allocates, so leaks are checked through counts
+        let armed = Cell::new(false);
+        let cmp = |a: &Rc<i32>, b: &Rc<i32>| {
+            if armed.get() && (**a == 13 || **b == 13) {
+                panic!("comparator failed");
+            }
+            a.cmp(b)
+        };
+        let values: Vec<Rc<i32>> = (0..20).map(Rc::new).collect();
+        let mut even = BinarySearchTree::from_sorted_by(values.iter().step_by(2).cloned(), cmp);
+        let odd_values = values.iter().skip(1).step_by(2).cloned();
+        let mut odd = BinarySearchTree::from_sorted_by(odd_values, cmp);
+
+        armed.set(true);
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| even.append(&mut odd)));
+        assert!(result.is_err());
+        armed.set(false);
+
+        // Both trees stay usable, and every node was freed exactly once
+        assert!(even.is_valid_bst() && odd.is_valid_bst());
+        even.add(Rc::clone(&values[0]));
+        drop((even, odd));
+        assert!(values.iter().all(|value| Rc::strong_count(value) == 1));
     }
 }
@@ -0,0 +1,246 @@
+use std::cmp::Ordering;
+
+/// Decides how the values of a [`BinarySearchTree`](super::BinarySearchTree)
+/// are ordered.
+///
+/// The ordering must stay the same for as long as the values are in the tree,
+/// and values it reports as equal are treated as duplicates. Closures taking
+/// two references and returning an [`Ordering`] implement this trait, so
+/// one-off orderings don't need a type of their own. Their parameter types
+/// have to be written out, since they can't be inferred through the trait.
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BinarySearchTree;
+///
+/// struct Job {
+///     priority: u8,
+///     name: &'static str,
+/// }
+///
+/// // Ordered by a single field
+/// let by_priority = |a: &Job, b: &Job| a.priority.cmp(&b.priority);
+/// let build = Job { priority: 2, name: "build" };
+/// let mut jobs = BinarySearchTree::with_comparator(build, by_priority);
+/// jobs.add(Job { priority: 1, name: "fetch" });
+///
+/// assert_eq!(jobs.min().map(|job| job.name), Some("fetch"));
+/// ```
+pub trait Comparator<T: ?Sized> {
+    /// Compares `a` with `b`
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+
+    /// Compares `a` with `b`, returning `None` when they can't be ordered.
+    /// Lookups and removals use it to report no match for such values instead
+    /// of mistaking them for equal ones. Defaults to
+    /// [`compare`](Comparator::compare), for orderings that are total
+    fn partial_compare(&self, a: &T, b: &T) -> Option<Ordering> {
+        Some(self.compare(a, b))
+    }
+}
+
+impl<T: ?Sized, F> Comparator<T> for F
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// Orders values by their [`PartialOrd`] implementation. This is the default
+/// comparator of a [`BinarySearchTree`](super::BinarySearchTree).
+///
+/// Values that can't be compared, like NaN, have no place in the order: they
+/// are never added to a tree that already holds a value, and looking them up
+/// or removing them finds nothing. Ranges and ranks treat them as equal to
+/// the values they can't be compared with. Use [`TotalOrder`] to keep them
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BinarySearchTree;
+///
+/// let mut tree = BinarySearchTree::new(1.0);
+/// tree.add(f64::NAN);
+///
+/// assert_eq!(tree.len(), 1);
+/// assert_eq!(tree.get(&f64::NAN), None);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NaturalOrder;
+
+impl<T: PartialOrd + ?Sized> Comparator<T> for NaturalOrder {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.partial_cmp(b).unwrap_or(Ordering::Equal)
+    }
+
+    fn partial_compare(&self, a: &T, b: &T) -> Option<Ordering> {
+        a.partial_cmp(b)
+    }
+}
+
+/// Orders values by their [`PartialOrd`] implementation, largest first
+///
+/// ```
+/// use dsa_abc::binary_search_tree::{BinarySearchTree, ReverseOrder};
+///
+/// let tree: BinarySearchTree<i32, ReverseOrder> = [1, 3, 2].into_iter().collect();
+/// assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&3, &2, &1]);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReverseOrder;
+
+impl<T: PartialOrd + ?Sized> Comparator<T> for ReverseOrder {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        NaturalOrder.compare(b, a)
+    }
+
+    fn partial_compare(&self, a: &T, b: &T) -> Option<Ordering> {
+        b.partial_cmp(a)
+    }
+}
+
+/// Orders values by their [`PartialOrd`] implementation, placing the values
+/// that can't even be compared with themselves, like NaN, after every other
+/// value. Those are all equal to each other, so a tree keeps one of them.
+///
+/// # Panics
+///
+/// Panics when comparing two values that can't be compared with each other
+/// but can with themselves, since no consistent place exists for them
+///
+/// ```
+/// use dsa_abc::binary_search_tree::{BinarySearchTree, TotalOrder};
+///
+/// let mut tree = BinarySearchTree::with_comparator(1.0, TotalOrder);
+/// tree.add(f64::NAN);
+/// tree.add(-1.0);
+///
+/// assert_eq!(tree.len(), 3);
+/// assert!(tree.max().unwrap().is_nan());
+/// assert_eq!(tree.min(), Some(&-1.0));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TotalOrder;
+
+impl<T: PartialOrd + ?Sized> Comparator<T> for TotalOrder {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        if let Some(order) = a.partial_cmp(b) {
+            return order;
+        }
+
+        let a_unordered = a.partial_cmp(a).is_none();
+        let b_unordered = b.partial_cmp(b).is_none();
+        match (a_unordered, b_unordered) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => panic!("values are not totally ordered"),
+        }
+    }
+}
+
+/// Orders values by the key `F` extracts from them, using the key's
+/// [`PartialOrd`] implementation. Values with equal keys are duplicates, so
+/// the rest of a value is payload that can be changed in place with
+/// [`BinarySearchTree::get_mut`](super::BinarySearchTree::get_mut), and
+/// looked up by key alone with
+/// [`BinarySearchTree::get_by_key`](super::BinarySearchTree::get_by_key).
+///
+/// The key is returned by value and extracted on every comparison, so it
+/// should be cheap to produce, like a field that is `Copy`
+///
+/// ```
+/// use dsa_abc::binary_search_tree::{BinarySearchTree, ByKey};
+///
+/// struct User {
+///     id: u32,
+///     name: &'static str,
+/// }
+///
+/// let mut users = BinarySearchTree::with_comparator(
+///     User { id: 7, name: "ada" },
+///     ByKey(|user: &User| user.id),
+/// );
+/// users.add(User { id: 3, name: "alan" });
+///
+/// users.get_mut_by_key(&7).unwrap().name = "grace";
+/// assert_eq!(users.get_by_key(&7).map(|user| user.name), Some("grace"));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ByKey<F>(pub F);
+
+impl<T, K, F> Comparator<T> for ByKey<F>
+where
+    K: PartialOrd,
+    F: Fn(&T) -> K,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        NaturalOrder.compare(&(self.0)(a), &(self.0)(b))
+    }
+
+    fn partial_compare(&self, a: &T, b: &T) -> Option<Ordering> {
+        (self.0)(a).partial_cmp(&(self.0)(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_and_reverse() {
+        assert_eq!(NaturalOrder.compare(&1, &2), Ordering::Less);
+        assert_eq!(ReverseOrder.compare(&1, &2), Ordering::Greater);
+        assert_eq!(NaturalOrder.compare(&f64::NAN, &1.0), Ordering::Equal);
+        assert_eq!(ReverseOrder.compare("b", "a"), Ordering::Less);
+        assert_eq!(NaturalOrder.partial_compare(&f64::NAN, &1.0), None);
+        assert_eq!(
+            ReverseOrder.partial_compare(&1, &2),
+            Some(Ordering::Greater)
+        );
+        let nan_last = TotalOrder.partial_compare(&f64::NAN, &1.0);
+        assert_eq!(nan_last, Some(Ordering::Greater));
+    }
+
+    #[test]
+    fn total_order_places_nan_last() {
+        assert_eq!(TotalOrder.compare(&f64::NAN, &1.0), Ordering::Greater);
+        assert_eq!(TotalOrder.compare(&1.0, &f64::NAN), Ordering::Less);
+        assert_eq!(TotalOrder.compare(&f64::NAN, &f64::NAN), Ordering::Equal);
+        assert_eq!(TotalOrder.compare(&-0.0, &0.0), Ordering::Equal);
+    }
+
+    #[test]
+    #[should_panic(expected = "not totally ordered")]
+    fn total_order_rejects_incomparable_values() {
+        // Sets ordered by inclusion: neither is a subset of the other
+        #[derive(PartialEq)]
+        struct Bits(u8);
+
+        impl PartialOrd for Bits {
+            fn partial_cmp(&self, other: &Bits) -> Option<Ordering> {
+                match (self.0 & other.0 == self.0, self.0 & other.0 == other.0) {
+                    (true, true) => Some(Ordering::Equal),
+                    (true, false) => Some(Ordering::Less),
+                    (false, true) => Some(Ordering::Greater),
+                    (false, false) => None,
+                }
+            }
+        }
+
+        TotalOrder.compare(&Bits(0b01), &Bits(0b10));
+    }
+
+    #[test]
+    fn by_key_ignores_the_rest() {
+        let by_first = ByKey(|pair: &(i32, &str)| pair.0);
+        assert_eq!(by_first.compare(&(1, "z"), &(2, "a")), Ordering::Less);
+        assert_eq!(by_first.compare(&(2, "z"), &(2, "a")), Ordering::Equal);
+    }
+
+    #[test]
+    fn closures_are_comparators() {
+        let by_len = |a: &&str, b: &&str| a.len().cmp(&b.len());
+        assert_eq!(by_len.compare(&"ab", &"c"), Ordering::Greater);
+        assert_eq!(by_len.compare(&"ab", &"cd"), Ordering::Equal);
+    }
+}
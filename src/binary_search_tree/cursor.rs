@@ -0,0 +1,730 @@
+use std::cmp::Ordering;
+use std::ptr;
+
+use super::{
+    leftmost, remove_linked, rightmost, BinarySearchTree, Comparator, NaturalOrder,
+    Node,
+};
+
+/// Nodes from the root down to the one a cursor points at. Empty when the
+/// cursor is on the ghost position, which sits after the largest value and
+/// before the smallest one
+struct Path<T>(Vec<*mut Node<T>>);
+
+impl<T> Path<T> {
+    /// Path to the smallest node of the tree rooted at `root`
+    unsafe fn front(root: *mut Node<T>) -> Path<T> {
+        let mut path = Path(vec![]);
+        unsafe { path.push_left(root) };
+        path
+    }
+
+    /// Path to the largest node of the tree rooted at `root`
+    unsafe fn back(root: *mut Node<T>) -> Path<T> {
+        let mut path = Path(vec![]);
+        unsafe { path.push_right(root) };
+        path
+    }
+
+    /// Path to the first node `cmp` doesn't report as below the searched
+    /// value, where `cmp` compares the searched value with a node's data
+    unsafe fn lower_bound<F>(root: *mut Node<T>, mut cmp: F) -> Path<T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        let mut path = vec![];
+        let mut keep = 0;
+        let mut node = root;
+        unsafe {
+            while !node.is_null() {
+                path.push(node);
+                match cmp(&(*node).data) {
+                    Ordering::Greater => node = (*node).right,
+                    Ordering::Less => {
+                        keep = path.len();
+                        node = (*node).left;
+                    }
+                    Ordering::Equal => {
+                        keep = path.len();
+                        break;
+                    }
+                }
+            }
+        }
+        // The last candidate is on the search path, so its own path is a prefix
+        path.truncate(keep);
+        Path(path)
+    }
+
+    /// Path to the last node `cmp` doesn't report as above the searched
+    /// value, where `cmp` compares the searched value with a node's data
+    unsafe fn upper_bound<F>(root: *mut Node<T>, mut cmp: F) -> Path<T>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        let mut path = vec![];
+        let mut keep = 0;
+        let mut node = root;
+        unsafe {
+            while !node.is_null() {
+                path.push(node);
+                match cmp(&(*node).data) {
+                    Ordering::Less => node = (*node).left,
+                    Ordering::Greater => {
+                        keep = path.len();
+                        node = (*node).right;
+                    }
+                    Ordering::Equal => {
+                        keep = path.len();
+                        break;
+                    }
+                }
+            }
+        }
+        path.truncate(keep);
+        Path(path)
+    }
+
+    /// Node the path ends at, or null on the ghost position
+    fn current(&self) -> *mut Node<T> {
+        self.0.last().copied().unwrap_or(ptr::null_mut())
+    }
+
+    /// Pushes `node` and its chain of left children
+    unsafe fn push_left(&mut self, mut node: *mut Node<T>) {
+        while !node.is_null() {
+            self.0.push(node);
+            node = unsafe { (*node).left };
+        }
+    }
+
+    /// Pushes `node` and its chain of right children
+    unsafe fn push_right(&mut self, mut node: *mut Node<T>) {
+        while !node.is_null() {
+            self.0.push(node);
+            node = unsafe { (*node).right };
+        }
+    }
+
+    /// In order successor of the current node without moving, or the
+    /// smallest node when on the ghost position. Null past the largest node
+    unsafe fn peek_next(&self, root: *mut Node<T>) -> *mut Node<T> {
+        unsafe {
+            let Some(&node) = self.0.last() else {
+                return leftmost(root);
+            };
+            if !(*node).right.is_null() {
+                return leftmost((*node).right);
+            }
+
+            // Nearest ancestor holding the current node in its left subtree
+            let mut child = node;
+            for &parent in self.0.iter().rev().skip(1) {
+                if (*parent).left == child {
+                    return parent;
+                }
+                child = parent;
+            }
+            ptr::null_mut()
+        }
+    }
+
+    /// In order predecessor of the current node without moving, or the
+    /// largest node when on the ghost position. Null before the smallest node
+    unsafe fn peek_prev(&self, root: *mut Node<T>) -> *mut Node<T> {
+        unsafe {
+            let Some(&node) = self.0.last() else {
+                return rightmost(root);
+            };
+            if !(*node).left.is_null() {
+                return rightmost((*node).left);
+            }
+
+            let mut child = node;
+            for &parent in self.0.iter().rev().skip(1) {
+                if (*parent).right == child {
+                    return parent;
+                }
+                child = parent;
+            }
+            ptr::null_mut()
+        }
+    }
+
+    /// Moves to the in order successor. O(1) amortized time complexity
+    unsafe fn move_next(&mut self, root: *mut Node<T>) {
+        unsafe {
+            let Some(&node) = self.0.last() else {
+                return self.push_left(root);
+            };
+            if !(*node).right.is_null() {
+                return self.push_left((*node).right);
+            }
+
+            let mut child = node;
+            self.0.pop();
+            while let Some(&parent) = self.0.last() {
+                if (*parent).left == child {
+                    return;
+                }
+                child = parent;
+                self.0.pop();
+            }
+        }
+    }
+
+    /// Moves to the in order predecessor. O(1) amortized time complexity
+    unsafe fn move_prev(&mut self, root: *mut Node<T>) {
+        unsafe {
+            let Some(&node) = self.0.last() else {
+                return self.push_right(root);
+            };
+            if !(*node).left.is_null() {
+                return self.push_right((*node).left);
+            }
+
+            let mut child = node;
+            self.0.pop();
+            while let Some(&parent) = self.0.last() {
+                if (*parent).right == child {
+                    return;
+                }
+                child = parent;
+                self.0.pop();
+            }
+        }
+    }
+}
+
+impl<T> Clone for Path<T> {
+    fn clone(&self) -> Path<T> {
+        Path(self.0.clone())
+    }
+}
+
+/// Position in a [`BinarySearchTree`], which can be moved to the neighbouring
+/// values in order. Created by [`BinarySearchTree::cursor_front`],
+/// [`BinarySearchTree::cursor_back`], [`BinarySearchTree::lower_bound`] and
+/// [`BinarySearchTree::upper_bound`].
+///
+/// Besides the values, a cursor can point at a "ghost" position between the
+/// largest value and the smallest one, where [`current`](Cursor::current)
+/// returns `None`. Moving forward from the largest value reaches the ghost,
+/// and moving forward again wraps around to the smallest value.
+///
+/// The cursor keeps the path from the root down to its value, so moving is
+/// O(1) amortized and O(h) in the worst case, using O(h) space.
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BinarySearchTree;
+///
+/// let tree: BinarySearchTree<i32> = [10, 20, 30, 40].into_iter().collect();
+///
+/// let mut cursor = tree.lower_bound(&25);
+/// assert_eq!(cursor.current(), Some(&30));
+/// cursor.move_prev();
+/// assert_eq!(cursor.current(), Some(&20));
+/// assert_eq!(cursor.peek_next(), Some(&30));
+///
+/// let mut cursor = tree.cursor_back();
+/// cursor.move_next();
+/// assert_eq!(cursor.current(), None);
+/// cursor.move_next();
+/// assert_eq!(cursor.current(), Some(&10));
+/// ```
+pub struct Cursor<'a, T, C = NaturalOrder> {
+    path: Path<T>,
+    tree: &'a BinarySearchTree<T, C>,
+}
+
+impl<'a, T, C> Cursor<'a, T, C> {
+    /// Moves the cursor to the next value in order, or to the ghost position
+    /// after the largest one. From the ghost position, moves to the smallest
+    /// value
+    pub fn move_next(&mut self) {
+        unsafe { self.path.move_next(self.tree.root) }
+    }
+
+    /// Moves the cursor to the previous value in order, or to the ghost
+    /// position before the smallest one. From the ghost position, moves to the
+    /// largest value
+    pub fn move_prev(&mut self) {
+        unsafe { self.path.move_prev(self.tree.root) }
+    }
+
+    /// Returns the value the cursor points at, or `None` on the ghost
+    /// position
+    pub fn current(&self) -> Option<&'a T> {
+        unsafe { self.path.current().as_ref().map(|node| &node.data) }
+    }
+
+    /// Returns the value [`move_next`](Self::move_next) would move to, without
+    /// moving. O(h) time complexity
+    pub fn peek_next(&self) -> Option<&'a T> {
+        unsafe {
+            self.path
+                .peek_next(self.tree.root)
+                .as_ref()
+                .map(|node| &node.data)
+        }
+    }
+
+    /// Returns the value [`move_prev`](Self::move_prev) would move to, without
+    /// moving. O(h) time complexity
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        unsafe {
+            self.path
+                .peek_prev(self.tree.root)
+                .as_ref()
+                .map(|node| &node.data)
+        }
+    }
+}
+
+impl<T, C> Clone for Cursor<'_, T, C> {
+    fn clone(&self) -> Self {
+        Cursor {
+            path: self.path.clone(),
+            tree: self.tree,
+        }
+    }
+}
+
+/// Position in a [`BinarySearchTree`] that can also add and remove values
+/// around itself. Created by [`BinarySearchTree::cursor_front_mut`],
+/// [`BinarySearchTree::cursor_back_mut`],
+/// [`BinarySearchTree::lower_bound_mut`] and
+/// [`BinarySearchTree::upper_bound_mut`].
+///
+/// Moves like a [`Cursor`], ghost position included. Values are only handed
+/// out as shared references, since changing them could break the ordering.
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BinarySearchTree;
+///
+/// let mut tree: BinarySearchTree<i32> = [1, 2, 3, 4, 5, 6].into_iter().collect();
+///
+/// // Removes the odd values, moving to the next value after each removal
+/// let mut cursor = tree.cursor_front_mut();
+/// while let Some(&value) = cursor.current() {
+///     if value % 2 == 1 {
+///         cursor.remove_current();
+///     } else {
+///         cursor.move_next();
+///     }
+/// }
+///
+/// let mut cursor = tree.lower_bound_mut(&4);
+/// cursor.insert_after(5);
+/// assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&2, &4, &5, &6]);
+/// ```
+pub struct CursorMut<'a, T, C = NaturalOrder> {
+    path: Path<T>,
+    tree: &'a mut BinarySearchTree<T, C>,
+}
+
+impl<'a, T, C> CursorMut<'a, T, C> {
+    /// Moves the cursor to the next value in order, or to the ghost position
+    /// after the largest one. From the ghost position, moves to the smallest
+    /// value
+    pub fn move_next(&mut self) {
+        unsafe { self.path.move_next(self.tree.root) }
+    }
+
+    /// Moves the cursor to the previous value in order, or to the ghost
+    /// position before the smallest one. From the ghost position, moves to the
+    /// largest value
+    pub fn move_prev(&mut self) {
+        unsafe { self.path.move_prev(self.tree.root) }
+    }
+
+    /// Returns the value the cursor points at, or `None` on the ghost
+    /// position
+    pub fn current(&self) -> Option<&T> {
+        unsafe { self.path.current().as_ref().map(|node| &node.data) }
+    }
+
+    /// Returns the value [`move_next`](Self::move_next) would move to, without
+    /// moving. O(h) time complexity
+    pub fn peek_next(&self) -> Option<&T> {
+        self.as_cursor().peek_next()
+    }
+
+    /// Returns the value [`move_prev`](Self::move_prev) would move to, without
+    /// moving. O(h) time complexity
+    pub fn peek_prev(&self) -> Option<&T> {
+        self.as_cursor().peek_prev()
+    }
+
+    /// Returns a read-only cursor at the same position, borrowing this one
+    pub fn as_cursor(&self) -> Cursor<'_, T, C> {
+        Cursor {
+            path: self.path.clone(),
+            tree: self.tree,
+        }
+    }
+
+    /// Removes the value the cursor points at and returns it, moving the
+    /// cursor to the next value in order. Returns `None` and does nothing on
+    /// the ghost position. O(h) time complexity
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.path.current();
+        if node.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let ancestors = &self.path.0[..self.path.0.len() - 1];
+            let link = match ancestors.last() {
+                None => ptr::addr_of_mut!(self.tree.root),
+                Some(&parent) if (*parent).left == node => ptr::addr_of_mut!((*parent).left),
+                Some(&parent) => ptr::addr_of_mut!((*parent).right),
+            };
+            for &ancestor in ancestors {
+                (*ancestor).size -= 1;
+            }
+
+            let (left, right) = ((*node).left, (*node).right);
+            if right.is_null() {
+                // The next value is above the node, so the path is moved
+                // while the node is still linked
+                self.path.move_next(self.tree.root);
+                return Some(remove_linked(link));
+            }
+
+            let data = remove_linked(link);
+            self.path.0.pop();
+            if left.is_null() {
+                // The right child took the node's place
+                self.path.push_left(*link);
+            } else {
+                // The successor took the node's place
+                self.path.0.push(*link);
+            }
+            Some(data)
+        }
+    }
+}
+
+impl<T, C: Comparator<T>> CursorMut<'_, T, C> {
+    /// Adds `value` right after the one the cursor points at, or as the new
+    /// smallest value on the ghost position. The cursor doesn't move.
+    /// O(h) time complexity
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `value` is above the current value and below the next
+    /// one, since it couldn't be found there otherwise
+    pub fn insert_after(&mut self, value: T) {
+        let tree = &mut *self.tree;
+        unsafe {
+            let current = self.path.current();
+            let next = self.path.peek_next(tree.root);
+            let fits = (current.is_null()
+                || tree.comparator.compare(&value, &(*current).data) == Ordering::Greater)
+                && (next.is_null()
+                    || tree.comparator.compare(&value, &(*next).data) == Ordering::Less);
+            assert!(
+                fits,
+                "insert_after needs a value between the current one and the next"
+            );
+
+            // The gap between two neighbours is the empty right link of the
+            // first one, or else the empty left link of the second one
+            let link = if !current.is_null() && (*current).right.is_null() {
+                ptr::addr_of_mut!((*current).right)
+            } else if next.is_null() {
+                ptr::addr_of_mut!(tree.root)
+            } else {
+                ptr::addr_of_mut!((*next).left)
+            };
+            let added = Node::new_mut(value);
+            *link = added;
+
+            // The new node hangs below the cursor's path, at the end of the
+            // chain of left children that starts right after the current node
+            for &ancestor in &self.path.0 {
+                (*ancestor).size += 1;
+            }
+            let mut node = match current.as_ref() {
+                None => tree.root,
+                Some(current) => current.right,
+            };
+            while node != added {
+                (*node).size += 1;
+                node = (*node).left;
+            }
+        }
+    }
+}
+
+impl<T, C: Comparator<T>> BinarySearchTree<T, C> {
+    /// Returns a cursor pointing at the smallest value, or at the ghost
+    /// position if the tree is empty. O(h) time complexity
+    pub fn cursor_front(&self) -> Cursor<'_, T, C> {
+        Cursor {
+            path: unsafe { Path::front(self.root) },
+            tree: self,
+        }
+    }
+
+    /// Returns a cursor pointing at the largest value, or at the ghost
+    /// position if the tree is empty. O(h) time complexity
+    pub fn cursor_back(&self) -> Cursor<'_, T, C> {
+        Cursor {
+            path: unsafe { Path::back(self.root) },
+            tree: self,
+        }
+    }
+
+    /// Returns a cursor pointing at the smallest value greater than or equal
+    /// to `data`, or at the ghost position if there is none.
+    /// O(h) time complexity
+    pub fn lower_bound(&self, data: &T) -> Cursor<'_, T, C> {
+        let cmp = |node: &T| self.comparator.compare(data, node);
+        Cursor {
+            path: unsafe { Path::lower_bound(self.root, cmp) },
+            tree: self,
+        }
+    }
+
+    /// Returns a cursor pointing at the largest value less than or equal to
+    /// `data`, or at the ghost position if there is none.
+    /// O(h) time complexity
+    pub fn upper_bound(&self, data: &T) -> Cursor<'_, T, C> {
+        let cmp = |node: &T| self.comparator.compare(data, node);
+        Cursor {
+            path: unsafe { Path::upper_bound(self.root, cmp) },
+            tree: self,
+        }
+    }
+
+    /// Mutable version of [`cursor_front`](Self::cursor_front)
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T, C> {
+        CursorMut {
+            path: unsafe { Path::front(self.root) },
+            tree: self,
+        }
+    }
+
+    /// Mutable version of [`cursor_back`](Self::cursor_back)
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T, C> {
+        CursorMut {
+            path: unsafe { Path::back(self.root) },
+            tree: self,
+        }
+    }
+
+    /// Mutable version of [`lower_bound`](Self::lower_bound)
+    pub fn lower_bound_mut(&mut self, data: &T) -> CursorMut<'_, T, C> {
+        let cmp = |node: &T| self.comparator.compare(data, node);
+        CursorMut {
+            path: unsafe { Path::lower_bound(self.root, cmp) },
+            tree: self,
+        }
+    }
+
+    /// Mutable version of [`upper_bound`](Self::upper_bound)
+    pub fn upper_bound_mut(&mut self, data: &T) -> CursorMut<'_, T, C> {
+        let cmp = |node: &T| self.comparator.compare(data, node);
+        CursorMut {
+            path: unsafe { Path::upper_bound(self.root, cmp) },
+            tree: self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::live_allocations;
+    use crate::xorshift::XorShift;
+    use std::collections::BTreeSet;
+
+    fn sample_tree() -> BinarySearchTree<i32> {
+        [10, 5, 15, 1, 9, 11, 30].into_iter().collect()
+    }
+
+    #[test]
+    fn walks_both_ways_through_ghost() {
+        let tree = sample_tree();
+
+        let mut cursor = tree.cursor_front();
+        let mut forward = vec![];
+        while let Some(&value) = cursor.current() {
+            forward.push(value);
+            cursor.move_next();
+        }
+        assert_eq!(forward, vec![1, 5, 9, 10, 11, 15, 30]);
+
+        // From the ghost position, moving back reaches the largest value
+        cursor.move_prev();
+        let mut backward = vec![];
+        while let Some(&value) = cursor.current() {
+            backward.push(value);
+            cursor.move_prev();
+        }
+        assert_eq!(backward, vec![30, 15, 11, 10, 9, 5, 1]);
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&1));
+        assert_eq!(cursor.peek_prev(), None);
+        assert_eq!(tree.cursor_back().peek_next(), None);
+    }
+
+    #[test]
+    fn bounds_and_peeks() {
+        let tree = sample_tree();
+
+        assert_eq!(tree.lower_bound(&9).current(), Some(&9));
+        assert_eq!(tree.lower_bound(&12).current(), Some(&15));
+        assert_eq!(tree.lower_bound(&31).current(), None);
+        assert_eq!(tree.upper_bound(&12).current(), Some(&11));
+        assert_eq!(tree.upper_bound(&0).current(), None);
+        assert_eq!(tree.upper_bound(&30).current(), Some(&30));
+
+        let cursor = tree.lower_bound(&10);
+        assert_eq!(cursor.peek_prev(), Some(&9));
+        assert_eq!(cursor.peek_next(), Some(&11));
+
+        // Peeking from the ghost position wraps around
+        let ghost = tree.lower_bound(&100);
+        assert_eq!(ghost.peek_next(), Some(&1));
+        assert_eq!(ghost.peek_prev(), Some(&30));
+
+        let empty = BinarySearchTree::<i32>::new_empty();
+        let mut cursor = empty.cursor_front();
+        assert_eq!(cursor.current(), None);
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_prev(), None);
+    }
+
+    #[test]
+    fn merge_join_with_two_cursors() {
+        let a: BinarySearchTree<i32> = [1, 3, 4, 7, 9, 12].into_iter().collect();
+        let b: BinarySearchTree<i32> = [2, 3, 7, 8, 12, 20].into_iter().collect();
+
+        let (mut left, mut right) = (a.cursor_front(), b.cursor_front());
+        let mut common = vec![];
+        while let (Some(&x), Some(&y)) = (left.current(), right.current()) {
+            match x.cmp(&y) {
+                Ordering::Less => left.move_next(),
+                Ordering::Greater => right.move_next(),
+                Ordering::Equal => {
+                    common.push(x);
+                    left.move_next();
+                    right.move_next();
+                }
+            }
+        }
+        assert_eq!(common, vec![3, 7, 12]);
+    }
+
+    #[test]
+    fn insert_after_fills_gaps() {
+        let mut tree: BinarySearchTree<i32> = [10, 20, 30].into_iter().collect();
+
+        // Right link of a leaf, then left link of the next value
+        tree.lower_bound_mut(&10).insert_after(15);
+        tree.lower_bound_mut(&15).insert_after(17);
+        tree.lower_bound_mut(&20).insert_after(25);
+        // The ghost position adds a new smallest value
+        let mut cursor = tree.lower_bound_mut(&40);
+        cursor.insert_after(0);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&0));
+
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![0, 10, 15, 17, 20, 25, 30]
+        );
+        assert!(tree.is_valid_bst());
+
+        let mut empty = BinarySearchTree::<i32>::new_empty();
+        empty.cursor_front_mut().insert_after(7);
+        assert_eq!(empty.get(&7), Some(&7));
+        assert_eq!(empty.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "between the current one and the next")]
+    fn insert_after_rejects_out_of_order_values() {
+        let mut tree = sample_tree();
+        tree.lower_bound_mut(&10).insert_after(12);
+    }
+
+    #[test]
+    fn remove_current_moves_to_next() {
+        let mut tree = sample_tree();
+
+        // Two children: the successor takes the node's place
+        let mut cursor = tree.lower_bound_mut(&10);
+        assert_eq!(cursor.remove_current(), Some(10));
+        assert_eq!(cursor.current(), Some(&11));
+        assert_eq!(cursor.peek_prev(), Some(&9));
+
+        // Leaf with no successor below it
+        let mut cursor = tree.lower_bound_mut(&9);
+        assert_eq!(cursor.remove_current(), Some(9));
+        assert_eq!(cursor.current(), Some(&11));
+
+        // The largest value leaves the cursor on the ghost position
+        let mut cursor = tree.cursor_back_mut();
+        assert_eq!(cursor.remove_current(), Some(30));
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.remove_current(), None);
+
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), vec![1, 5, 11, 15]);
+        assert!(tree.is_valid_bst());
+
+        let mut cursor = tree.cursor_front_mut();
+        while cursor.remove_current().is_some() {}
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn matches_btreeset() {
+        let before = live_allocations();
+        {
+            let mut rng = XorShift(0xBB67_AE85_84CA_A73B);
+
+            let mut tree: BinarySearchTree<u64> = (0..200).step_by(4).collect();
+            let mut expected: BTreeSet<u64> = (0..200).step_by(4).collect();
+            for _ in 0..2_000 {
+                let value = rng.next() % 200;
+                let mut cursor = tree.lower_bound_mut(&value);
+                let found = expected.range(value..).next().copied();
+                assert_eq!(cursor.current().copied(), found);
+
+                match rng.next() % 3 {
+                    0 => {
+                        assert_eq!(cursor.remove_current(), found);
+                        if let Some(found) = found {
+                            expected.remove(&found);
+                        }
+                        let after = found.and_then(|found| expected.range(found..).next());
+                        assert_eq!(cursor.current(), after);
+                    }
+                    1 => {
+                        let previous = expected.range(..value).next_back().copied();
+                        cursor.move_prev();
+                        assert_eq!(cursor.current().copied(), previous);
+                    }
+                    _ => {
+                        // Right after the found value, unless that's taken
+                        let gap = found.map(|found| found + 1);
+                        if let Some(gap) = gap.filter(|gap| !expected.contains(gap)) {
+                            cursor.insert_after(gap);
+                            expected.insert(gap);
+                        }
+                    }
+                }
+            }
+
+            assert!(tree.is_valid_bst());
+            assert!(tree.iter().eq(expected.iter()));
+        }
+        assert_eq!(live_allocations(), before);
+    }
+}
@@ -0,0 +1,798 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+
+use super::{flatten, Comparator, NaturalOrder, Node, Vine};
+
+/// Pushes `node` and its chain of left children into `stack`
+fn push_left<T>(stack: &mut Vec<&Node<T>>, mut node: *mut Node<T>) {
+    while let Some(current) = unsafe { node.as_ref() } {
+        stack.push(current);
+        node = current.left;
+    }
+}
+
+/// Pushes `node` and its chain of right children into `stack`
+fn push_right<T>(stack: &mut Vec<&Node<T>>, mut node: *mut Node<T>) {
+    while let Some(current) = unsafe { node.as_ref() } {
+        stack.push(current);
+        node = current.right;
+    }
+}
+
+/// In order iterator over the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree), created by
+/// [`BinarySearchTree::iter`](super::BinarySearchTree::iter).
+///
+/// Keeps one stack of pending nodes for each end, so the iterator can be
+/// walked from the front and from the back at the same time.
+pub struct Iter<'a, T> {
+    front: Vec<&'a Node<T>>,
+    back: Vec<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    pub(super) fn new(root: *mut Node<T>, len: usize) -> Iter<'a, T> {
+        let mut front = vec![];
+        let mut back = vec![];
+        push_left(&mut front, root);
+        push_right(&mut back, root);
+
+        Iter {
+            front,
+            back,
+            remaining: len,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.front.pop()?;
+        push_left(&mut self.front, node.right);
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.back.pop()?;
+        push_right(&mut self.back, node.left);
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+/// Pushes `node` and its chain of left children into `stack`, keeping mutable
+/// access to them
+fn push_left_mut<T>(stack: &mut Vec<*mut Node<T>>, mut node: *mut Node<T>) {
+    while !node.is_null() {
+        stack.push(node);
+        node = unsafe { (*node).left };
+    }
+}
+
+/// Pushes `node` and its chain of right children into `stack`, keeping mutable
+/// access to them
+fn push_right_mut<T>(stack: &mut Vec<*mut Node<T>>, mut node: *mut Node<T>) {
+    while !node.is_null() {
+        stack.push(node);
+        node = unsafe { (*node).right };
+    }
+}
+
+/// In order iterator over mutable references to the data of every node.
+///
+/// Not exposed on [`BinarySearchTree`](super::BinarySearchTree), since
+/// changing a value could break the ordering, but used by its `in_order_mut`
+/// callback and by the map variant to hand out its values
+pub(super) struct IterMut<'a, T> {
+    front: Vec<*mut Node<T>>,
+    back: Vec<*mut Node<T>>,
+    remaining: usize,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> IterMut<'a, T> {
+    pub(super) fn new(root: *mut Node<T>, len: usize) -> IterMut<'a, T> {
+        let mut front = vec![];
+        let mut back = vec![];
+        push_left_mut(&mut front, root);
+        push_right_mut(&mut back, root);
+
+        IterMut {
+            front,
+            back,
+            remaining: len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.front.pop()?;
+        unsafe {
+            push_left_mut(&mut self.front, (*node).right);
+            self.remaining -= 1;
+            Some(&mut (*node).data)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.back.pop()?;
+        unsafe {
+            push_right_mut(&mut self.back, (*node).left);
+            self.remaining -= 1;
+            Some(&mut (*node).data)
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+/// In order walk over the nodes whose data lies within a range, shared by the
+/// range iterators of the set and the map.
+///
+/// Each end keeps a stack seeded by a single descent towards its bound, so
+/// subtrees outside the range are never visited. Each end also knows the node
+/// where it must stop: initially the first node past the other bound, and
+/// afterwards the last node yielded by the other end
+pub(super) struct RawRange<T> {
+    front: Vec<*mut Node<T>>,
+    back: Vec<*mut Node<T>>,
+    front_end: *mut Node<T>,
+    back_end: *mut Node<T>,
+}
+
+impl<T> RawRange<T> {
+    /// `before` tells if some data is below the start of the range and
+    /// `after` if it is above its end. The start must not be above the end
+    pub(super) fn new<B, A>(root: *mut Node<T>, before: B, after: A) -> RawRange<T>
+    where
+        B: Fn(&T) -> bool,
+        A: Fn(&T) -> bool,
+    {
+        let mut range = RawRange {
+            front: vec![],
+            back: vec![],
+            front_end: ptr::null_mut(),
+            back_end: ptr::null_mut(),
+        };
+
+        unsafe {
+            let mut node = root;
+            while !node.is_null() {
+                if before(&(*node).data) {
+                    range.back_end = node;
+                    node = (*node).right;
+                } else {
+                    range.front.push(node);
+                    node = (*node).left;
+                }
+            }
+
+            let mut node = root;
+            while !node.is_null() {
+                if after(&(*node).data) {
+                    range.front_end = node;
+                    node = (*node).left;
+                } else {
+                    range.back.push(node);
+                    node = (*node).right;
+                }
+            }
+        }
+
+        range
+    }
+
+    pub(super) fn next(&mut self) -> Option<*mut Node<T>> {
+        let node = *self.front.last()?;
+        if node == self.front_end {
+            self.front.clear();
+            self.back.clear();
+            return None;
+        }
+
+        self.front.pop();
+        push_left_mut(&mut self.front, unsafe { (*node).right });
+        self.back_end = node;
+        Some(node)
+    }
+
+    pub(super) fn next_back(&mut self) -> Option<*mut Node<T>> {
+        let node = *self.back.last()?;
+        if node == self.back_end {
+            self.front.clear();
+            self.back.clear();
+            return None;
+        }
+
+        self.back.pop();
+        push_right_mut(&mut self.back, unsafe { (*node).left });
+        self.front_end = node;
+        Some(node)
+    }
+}
+
+/// In order iterator over the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree) that lie within a range,
+/// created by [`BinarySearchTree::range`](super::BinarySearchTree::range)
+pub struct Range<'a, T> {
+    raw: RawRange<T>,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Range<'a, T> {
+    pub(super) fn new(raw: RawRange<T>) -> Range<'a, T> {
+        Range {
+            raw,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.raw.next().map(|node| unsafe { &(*node).data })
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Range<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.raw.next_back().map(|node| unsafe { &(*node).data })
+    }
+}
+
+/// Like [`Range`], but handing out mutable references. Only used by the map
+/// variant, which exposes its values through it
+pub(super) struct RangeMut<'a, T> {
+    raw: RawRange<T>,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> RangeMut<'a, T> {
+    pub(super) fn new(raw: RawRange<T>) -> RangeMut<'a, T> {
+        RangeMut {
+            raw,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for RangeMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.raw.next().map(|node| unsafe { &mut (*node).data })
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for RangeMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        self.raw
+            .next_back()
+            .map(|node| unsafe { &mut (*node).data })
+    }
+}
+
+/// Pre order iterator over the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree), created by
+/// [`BinarySearchTree::pre_order_iter`](super::BinarySearchTree::pre_order_iter)
+pub struct PreOrderIter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> PreOrderIter<'a, T> {
+    pub(super) fn new(root: *mut Node<T>, len: usize) -> PreOrderIter<'a, T> {
+        PreOrderIter {
+            stack: unsafe { root.as_ref() }.into_iter().collect(),
+            remaining: len,
+        }
+    }
+}
+
+impl<'a, T> Iterator for PreOrderIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+
+        // Right is pushed first so the left subtree is visited before it
+        unsafe {
+            if let Some(right) = node.right.as_ref() {
+                self.stack.push(right);
+            }
+            if let Some(left) = node.left.as_ref() {
+                self.stack.push(left);
+            }
+        }
+
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for PreOrderIter<'_, T> {}
+
+/// Post order iterator over the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree), created by
+/// [`BinarySearchTree::post_order_iter`](super::BinarySearchTree::post_order_iter)
+pub struct PostOrderIter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> PostOrderIter<'a, T> {
+    pub(super) fn new(root: *mut Node<T>, len: usize) -> PostOrderIter<'a, T> {
+        let mut iter = PostOrderIter {
+            stack: vec![],
+            remaining: len,
+        };
+        iter.push_first_leaf(root);
+        iter
+    }
+
+    /// Pushes the path from `node` down to the first node visited in post
+    /// order within its subtree, preferring left children over right ones
+    fn push_first_leaf(&mut self, mut node: *mut Node<T>) {
+        while let Some(current) = unsafe { node.as_ref() } {
+            self.stack.push(current);
+            node = if current.left.is_null() {
+                current.right
+            } else {
+                current.left
+            };
+        }
+    }
+}
+
+impl<'a, T> Iterator for PostOrderIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+
+        // Coming back up from a left child, the parent's right subtree still
+        // has to be visited before the parent itself
+        if let Some(parent) = self.stack.last() {
+            if ptr::eq(parent.left, node) {
+                self.push_first_leaf(parent.right);
+            }
+        }
+
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for PostOrderIter<'_, T> {}
+
+/// Level order (breadth first) iterator over the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree), created by
+/// [`BinarySearchTree::level_order_iter`](super::BinarySearchTree::level_order_iter)
+pub struct LevelOrderIter<'a, T>(LevelOrderDepthIter<'a, T>);
+
+impl<'a, T> LevelOrderIter<'a, T> {
+    pub(super) fn new(root: *mut Node<T>, len: usize) -> LevelOrderIter<'a, T> {
+        LevelOrderIter(LevelOrderDepthIter::new(root, len))
+    }
+}
+
+impl<'a, T> Iterator for LevelOrderIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.next().map(|(_, data)| data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for LevelOrderIter<'_, T> {}
+
+/// Level order iterator over the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree) along with their depth, the
+/// root being at depth 0. Created by
+/// [`BinarySearchTree::level_order_depth_iter`](super::BinarySearchTree::level_order_depth_iter)
+pub struct LevelOrderDepthIter<'a, T> {
+    queue: VecDeque<(&'a Node<T>, usize)>,
+    remaining: usize,
+}
+
+impl<'a, T> LevelOrderDepthIter<'a, T> {
+    pub(super) fn new(root: *mut Node<T>, len: usize) -> LevelOrderDepthIter<'a, T> {
+        LevelOrderDepthIter {
+            queue: unsafe { root.as_ref() }
+                .map(|root| (root, 0))
+                .into_iter()
+                .collect(),
+            remaining: len,
+        }
+    }
+}
+
+impl<'a, T> Iterator for LevelOrderDepthIter<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<(usize, &'a T)> {
+        let (node, depth) = self.queue.pop_front()?;
+
+        unsafe {
+            if let Some(left) = node.left.as_ref() {
+                self.queue.push_back((left, depth + 1));
+            }
+            if let Some(right) = node.right.as_ref() {
+                self.queue.push_back((right, depth + 1));
+            }
+        }
+
+        self.remaining -= 1;
+        Some((depth, &node.data))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for LevelOrderDepthIter<'_, T> {}
+
+/// Walks two in order iterators side by side, the way merge sort merges runs.
+/// Shared by the set algebra iterators
+struct MergeIter<'a, T, C> {
+    a: Peekable<Iter<'a, T>>,
+    b: Peekable<Iter<'a, T>>,
+    comparator: &'a C,
+}
+
+impl<'a, T, C: Comparator<T>> MergeIter<'a, T, C> {
+    fn new(a: Iter<'a, T>, b: Iter<'a, T>, comparator: &'a C) -> MergeIter<'a, T, C> {
+        MergeIter {
+            a: a.peekable(),
+            b: b.peekable(),
+            comparator,
+        }
+    }
+
+    /// Advances past the smallest pending value. Both sides are returned when
+    /// they hold equal values, otherwise only the side that held the smaller
+    /// one
+    fn next_pair(&mut self) -> (Option<&'a T>, Option<&'a T>) {
+        let order = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => self.comparator.compare(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return (None, None),
+        };
+
+        match order {
+            Ordering::Less => (self.a.next(), None),
+            Ordering::Greater => (None, self.b.next()),
+            Ordering::Equal => (self.a.next(), self.b.next()),
+        }
+    }
+
+    fn lens(&self) -> (usize, usize) {
+        (self.a.len(), self.b.len())
+    }
+}
+
+/// Lazy iterator over the values in either of two
+/// [`BinarySearchTree`](super::BinarySearchTree)s, in order and without
+/// duplicates, created by
+/// [`BinarySearchTree::union`](super::BinarySearchTree::union)
+pub struct Union<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+impl<'a, T, C: Comparator<T>> Union<'a, T, C> {
+    pub(super) fn new(a: Iter<'a, T>, b: Iter<'a, T>, comparator: &'a C) -> Union<'a, T, C> {
+        Union(MergeIter::new(a, b, comparator))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> Iterator for Union<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let (a, b) = self.0.next_pair();
+        a.or(b)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = self.0.lens();
+        (a.max(b), Some(a + b))
+    }
+}
+
+/// Lazy iterator over the values in both of two
+/// [`BinarySearchTree`](super::BinarySearchTree)s, in order, created by
+/// [`BinarySearchTree::intersection`](super::BinarySearchTree::intersection)
+pub struct Intersection<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+impl<'a, T, C: Comparator<T>> Intersection<'a, T, C> {
+    pub(super) fn new(a: Iter<'a, T>, b: Iter<'a, T>, comparator: &'a C) -> Intersection<'a, T, C> {
+        Intersection(MergeIter::new(a, b, comparator))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> Iterator for Intersection<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.0.next_pair() {
+                (Some(a), Some(_)) => return Some(a),
+                (None, None) => return None,
+                // Nothing left to match once either side runs out
+                (Some(_), None) if self.0.b.peek().is_none() => return None,
+                (None, Some(_)) if self.0.a.peek().is_none() => return None,
+                _ => {}
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = self.0.lens();
+        (0, Some(a.min(b)))
+    }
+}
+
+/// Lazy iterator over the values of one
+/// [`BinarySearchTree`](super::BinarySearchTree) that are not in another, in
+/// order, created by
+/// [`BinarySearchTree::difference`](super::BinarySearchTree::difference)
+pub struct Difference<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+impl<'a, T, C: Comparator<T>> Difference<'a, T, C> {
+    pub(super) fn new(a: Iter<'a, T>, b: Iter<'a, T>, comparator: &'a C) -> Difference<'a, T, C> {
+        Difference(MergeIter::new(a, b, comparator))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> Iterator for Difference<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.0.next_pair() {
+                (Some(a), None) => return Some(a),
+                (None, None) => return None,
+                // Values only in the other tree can't be yielded anymore
+                (None, Some(_)) if self.0.a.peek().is_none() => return None,
+                _ => {}
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = self.0.lens();
+        (a.saturating_sub(b), Some(a))
+    }
+}
+
+/// Lazy iterator over the values in exactly one of two
+/// [`BinarySearchTree`](super::BinarySearchTree)s, in order, created by
+/// [`BinarySearchTree::symmetric_difference`](super::BinarySearchTree::symmetric_difference)
+pub struct SymmetricDifference<'a, T, C = NaturalOrder>(MergeIter<'a, T, C>);
+
+impl<'a, T, C: Comparator<T>> SymmetricDifference<'a, T, C> {
+    pub(super) fn new(
+        a: Iter<'a, T>,
+        b: Iter<'a, T>,
+        comparator: &'a C,
+    ) -> SymmetricDifference<'a, T, C> {
+        SymmetricDifference(MergeIter::new(a, b, comparator))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> Iterator for SymmetricDifference<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.0.next_pair() {
+                (Some(a), None) => return Some(a),
+                (None, Some(b)) => return Some(b),
+                (None, None) => return None,
+                (Some(_), Some(_)) => {}
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = self.0.lens();
+        (0, Some(a + b))
+    }
+}
+
+/// Owning in order iterator over the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree), created by its
+/// [`IntoIterator`] implementation.
+///
+/// Nodes are freed as they are yielded. Like the tree's `Drop`, it rotates
+/// left children up instead of keeping a stack, so it runs in O(1) space.
+pub struct IntoIter<T> {
+    current: *mut Node<T>,
+    remaining: usize,
+    marker: PhantomData<Box<Node<T>>>,
+}
+
+impl<T> IntoIter<T> {
+    pub(super) fn new(root: *mut Node<T>, len: usize) -> IntoIter<T> {
+        IntoIter {
+            current: root,
+            remaining: len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        unsafe {
+            while !self.current.is_null() {
+                let left = (*self.current).left;
+                if left.is_null() {
+                    let node = Box::from_raw(self.current);
+                    self.current = node.right;
+                    self.remaining -= 1;
+                    return Some(node.data);
+                }
+
+                (*self.current).left = (*left).right;
+                (*left).right = self.current;
+                self.current = left;
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
+
+/// Iterator that removes and yields the values of a
+/// [`BinarySearchTree`](super::BinarySearchTree) matching a predicate, in
+/// order. Created by
+/// [`BinarySearchTree::extract_if`](super::BinarySearchTree::extract_if).
+///
+/// The tree is flattened into a chain of nodes up front and the kept nodes
+/// are relinked as the chain is walked. When the iterator is dropped, the
+/// values it didn't reach are kept without calling the predicate, and the
+/// tree is rebuilt balanced. If the iterator is leaked, the tree is left
+/// empty and its nodes are never freed.
+pub struct ExtractIf<'a, T, F>
+where
+    F: FnMut(&T) -> bool,
+{
+    root: &'a mut *mut Node<T>,
+    kept: Vine<T>,
+    rest: *mut Node<T>,
+    rest_len: usize,
+    pred: F,
+}
+
+impl<'a, T, F> ExtractIf<'a, T, F>
+where
+    F: FnMut(&T) -> bool,
+{
+    pub(super) fn new(root: &'a mut *mut Node<T>, len: usize, pred: F) -> ExtractIf<'a, T, F> {
+        unsafe { flatten(root) };
+        let rest = mem::replace(root, ptr::null_mut());
+        ExtractIf {
+            root,
+            kept: Vine::new(),
+            rest,
+            rest_len: len,
+            pred,
+        }
+    }
+}
+
+impl<T, F> Iterator for ExtractIf<'_, T, F>
+where
+    F: FnMut(&T) -> bool,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        unsafe {
+            while !self.rest.is_null() {
+                // The node stays in the chain until the predicate returns, so
+                // it is kept if the predicate panics
+                let node = self.rest;
+                let extract = (self.pred)(&(*node).data);
+                self.rest = (*node).right;
+                self.rest_len -= 1;
+
+                if extract {
+                    return Some(Box::from_raw(node).data);
+                }
+                self.kept.push(node);
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.rest_len))
+    }
+}
+
+impl<T, F> Drop for ExtractIf<'_, T, F>
+where
+    F: FnMut(&T) -> bool,
+{
+    fn drop(&mut self) {
+        let mut kept = mem::replace(&mut self.kept, Vine::new());
+        unsafe {
+            kept.append_chain(self.rest, self.rest_len);
+        }
+        self.rest = ptr::null_mut();
+        *self.root = kept.into_tree();
+    }
+}
@@ -0,0 +1,1035 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::RangeBounds;
+use std::ptr;
+
+use super::{
+    check_bounds, count_between, find, find_link_turns, free_subtree, is_after, is_before, iter,
+    remove_linked, size, Node, Turns,
+};
+
+/// Ordered key/value map built on the same nodes as
+/// [`BinarySearchTree`](super::BinarySearchTree).
+///
+/// Every node stores a `(key, value)` pair and the tree is ordered by key
+/// only, so values don't need to implement any ordering trait. Lookups accept
+/// any borrowed form of the key, like the maps in `std::collections`.
+///
+/// ### Examples
+/// Here are some examples on how to use this structure
+///
+/// #### Inserting and updating
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BstMap;
+///
+/// let mut map = BstMap::new_empty();
+/// assert_eq!(map.insert(String::from("apples"), 3), None);
+/// // Inserting an existing key replaces and returns the old value
+/// assert_eq!(map.insert(String::from("apples"), 5), Some(3));
+///
+/// // Lookups can use `&str` for `String` keys
+/// if let Some(count) = map.get_mut("apples") {
+///     *count += 1;
+/// }
+/// assert_eq!(map.get("apples"), Some(&6));
+/// ```
+///
+/// #### Removing and iterating
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BstMap;
+///
+/// let mut map = BstMap::new(2, "two");
+/// map.insert(1, "one");
+/// map.insert(3, "three");
+///
+/// assert_eq!(map.remove(&2), Some("two"));
+/// assert!(!map.contains_key(&2));
+///
+/// // Keys are always visited in order
+/// assert_eq!(map.keys().collect::<Vec<_>>(), vec![&1, &3]);
+/// assert_eq!(map.values().collect::<Vec<_>>(), vec![&"one", &"three"]);
+/// ```
+///
+pub struct BstMap<K, V> {
+    root: *mut Node<(K, V)>,
+}
+
+impl<K: Ord, V> BstMap<K, V> {
+    /// Creates a new map with an initial entry as root
+    pub fn new(key: K, value: V) -> BstMap<K, V> {
+        BstMap {
+            root: Node::new_mut((key, value)),
+        }
+    }
+
+    /// Creates a new empty map
+    pub fn new_empty() -> BstMap<K, V> {
+        BstMap {
+            root: ptr::null_mut(),
+        }
+    }
+
+    /// Returns the number of entries in the map. O(1) time complexity
+    pub fn len(&self) -> usize {
+        unsafe { size(self.root) }
+    }
+
+    /// Returns `true` if the map holds no entries. O(1) time complexity
+    pub fn is_empty(&self) -> bool {
+        self.root.is_null()
+    }
+
+    /// Compares `key` with the key of a node's entry
+    fn compare<Q>(key: &Q, entry: &(K, V)) -> Ordering
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        key.cmp(entry.0.borrow())
+    }
+
+    /// Finds the node holding `key`, or a null pointer
+    fn find_node<Q>(&self, key: &Q) -> *mut Node<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        unsafe { find(self.root, |entry| Some(Self::compare(key, entry))) }
+    }
+
+    /// Inserts `value` under `key`. If the key was already present its value
+    /// is replaced and the old one returned, keeping the original key.
+    /// O(log n) time complexity
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut turns = Turns::new();
+        unsafe {
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |entry| {
+                Some(Self::compare(&key, entry))
+            });
+            if (*link).is_null() {
+                *link = Node::new_mut((key, value));
+                turns.resize(self.root, true);
+                None
+            } else {
+                Some(mem::replace(&mut (**link).data.1, value))
+            }
+        }
+    }
+
+    /// Gets the entry for `key` for in-place manipulation, descending from the
+    /// root only once. The turns taken on the way down are kept in the entry,
+    /// so inserting or removing through it fixes the sizes along them without
+    /// comparing keys again. O(log n) time complexity
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let mut turns = Turns::new();
+        unsafe {
+            let cmp = |entry: &(K, V)| Some(Self::compare(&key, entry));
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, cmp);
+            if (*link).is_null() {
+                Entry::Vacant(VacantEntry {
+                    key,
+                    link,
+                    root: ptr::addr_of_mut!(self.root),
+                    turns,
+                    marker: PhantomData,
+                })
+            } else {
+                Entry::Occupied(OccupiedEntry {
+                    link,
+                    root: ptr::addr_of_mut!(self.root),
+                    turns,
+                    marker: PhantomData,
+                })
+            }
+        }
+    }
+
+    /// Returns a reference to the value stored under `key`.
+    /// O(log n) time complexity
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        unsafe { self.find_node(key).as_ref().map(|node| &node.data.1) }
+    }
+
+    /// Returns a mutable reference to the value stored under `key`.
+    /// O(log n) time complexity
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        unsafe { self.find_node(key).as_mut().map(|node| &mut node.data.1) }
+    }
+
+    /// Returns `true` if the map holds an entry for `key`.
+    /// O(log n) time complexity
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        !self.find_node(key).is_null()
+    }
+
+    /// Removes the entry for `key` and returns its value, if any.
+    /// O(log n) time complexity
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut turns = Turns::new();
+        unsafe {
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |entry| {
+                Some(Self::compare(key, entry))
+            });
+            if (*link).is_null() {
+                None
+            } else {
+                turns.resize(self.root, false);
+                Some(remove_linked(link).1)
+            }
+        }
+    }
+
+    /// Returns an iterator over the entries of the map, ordered by key
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(iter::Iter::new(self.root, self.len()))
+    }
+
+    /// Returns an iterator over the entries of the map, ordered by key, with
+    /// mutable references to the values
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut(iter::IterMut::new(self.root, self.len()))
+    }
+
+    /// Returns an iterator over the keys of the map, in order
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys(self.iter())
+    }
+
+    /// Returns an iterator over the values of the map, ordered by key
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values(self.iter())
+    }
+
+    /// Returns an iterator over mutable references to the values of the map,
+    /// ordered by key
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut(self.iter_mut())
+    }
+
+    /// In order traversal with `on_find` callback receiving each key and a
+    /// mutable reference to its value. O(h) space complexity
+    pub fn in_order_mut<F>(&mut self, on_find: &mut F)
+    where
+        F: FnMut(&K, &mut V),
+    {
+        self.iter_mut().for_each(|(key, value)| on_find(key, value));
+    }
+
+    /// Builds the raw walk over the entries whose keys lie within `bounds`
+    fn raw_range<Q, R>(&self, bounds: R) -> iter::RawRange<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        check_bounds(bounds.start_bound(), bounds.end_bound(), Q::cmp);
+        iter::RawRange::new(
+            self.root,
+            |entry| is_before(entry.0.borrow(), bounds.start_bound(), Q::cmp),
+            |entry| is_after(entry.0.borrow(), bounds.end_bound(), Q::cmp),
+        )
+    }
+
+    /// Returns an iterator over the entries whose keys lie within `bounds`,
+    /// ordered by key. Only the subtrees that overlap the range are visited:
+    /// O(log n + k) time complexity for k entries
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is above its end, or if both ends are
+    /// excluded and equal
+    pub fn range<Q, R>(&self, bounds: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Range(iter::Range::new(self.raw_range(bounds)))
+    }
+
+    /// Like [`BstMap::range`], with mutable references to the values
+    ///
+    /// ```
+    /// use dsa_abc::binary_search_tree::BstMap;
+    ///
+    /// let mut prices = BstMap::new_empty();
+    /// for (level, amount) in [(100, 5), (101, 2), (102, 7), (103, 1)] {
+    ///     prices.insert(level, amount);
+    /// }
+    ///
+    /// for (_, amount) in prices.range_mut(101..=102) {
+    ///     *amount = 0;
+    /// }
+    /// assert_eq!(prices.values().collect::<Vec<_>>(), vec![&5, &0, &0, &1]);
+    /// ```
+    pub fn range_mut<Q, R>(&mut self, bounds: R) -> RangeMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        RangeMut(iter::RangeMut::new(self.raw_range(bounds)))
+    }
+
+    /// Returns how many keys lie within `bounds`, without visiting them: the
+    /// count is the difference between the ranks of both ends.
+    /// O(log n) time complexity
+    ///
+    /// # Panics
+    ///
+    /// Panics like [`BstMap::range`] on invalid bounds
+    pub fn count_range<Q, R>(&self, bounds: R) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        check_bounds(bounds.start_bound(), bounds.end_bound(), Q::cmp);
+        let (start, end) = (bounds.start_bound(), bounds.end_bound());
+        unsafe { count_between(self.root, start, end, Self::compare::<Q>) }
+    }
+}
+
+impl<K, V> Drop for BstMap<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.root);
+        }
+        self.root = ptr::null_mut();
+    }
+}
+
+impl<K, V> IntoIterator for BstMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// Consumes the map into an iterator over its entries, ordered by key
+    fn into_iter(mut self) -> IntoIter<K, V> {
+        let len = unsafe { size(self.root) };
+        let root = mem::replace(&mut self.root, ptr::null_mut());
+        IntoIter(iter::IntoIter::new(root, len))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a BstMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        Iter(iter::Iter::new(self.root, unsafe { size(self.root) }))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut BstMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        IterMut(iter::IterMut::new(self.root, unsafe { size(self.root) }))
+    }
+}
+
+/// A view into a single entry of a [`BstMap`], created by [`BstMap::entry`]
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    /// Returns the key of this entry
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Inserts `default` if the entry is vacant and returns a mutable
+    /// reference to the value
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Inserts the result of `default` if the entry is vacant and returns a
+    /// mutable reference to the value. `default` is only called when needed
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert_with`], but `default` receives the entry's key
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Inserts `V::default()` if the entry is vacant and returns a mutable
+    /// reference to the value
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` with the value if the entry is occupied, then returns the
+    /// entry so it can be chained with the `or_insert` methods
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// An occupied entry of a [`BstMap`], part of the [`Entry`] enum
+pub struct OccupiedEntry<'a, K, V> {
+    link: *mut *mut Node<(K, V)>,
+    root: *mut *mut Node<(K, V)>,
+    turns: Turns,
+    marker: PhantomData<&'a mut BstMap<K, V>>,
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    /// Returns the key of this entry
+    pub fn key(&self) -> &K {
+        unsafe { &(**self.link).data.0 }
+    }
+
+    /// Returns a reference to the value of this entry
+    pub fn get(&self) -> &V {
+        unsafe { &(**self.link).data.1 }
+    }
+
+    /// Returns a mutable reference to the value of this entry
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut (**self.link).data.1 }
+    }
+
+    /// Converts the entry into a mutable reference to its value, bound to the
+    /// map's lifetime
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { &mut (**self.link).data.1 }
+    }
+
+    /// Replaces the value of this entry and returns the old one
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes this entry from the map and returns its value
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes this entry from the map and returns its key and value
+    pub fn remove_entry(self) -> (K, V) {
+        unsafe {
+            self.turns.resize(*self.root, false);
+            remove_linked(self.link)
+        }
+    }
+}
+
+/// A vacant entry of a [`BstMap`], part of the [`Entry`] enum
+pub struct VacantEntry<'a, K, V> {
+    key: K,
+    link: *mut *mut Node<(K, V)>,
+    root: *mut *mut Node<(K, V)>,
+    turns: Turns,
+    marker: PhantomData<&'a mut BstMap<K, V>>,
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    /// Returns the key that would be used when inserting
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes ownership of the key back
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts `value` under the entry's key and returns a mutable reference
+    /// to it. The node is linked into the slot found by [`BstMap::entry`] and
+    /// the sizes along the recorded turns are grown, so no key is compared
+    /// again
+    pub fn insert(self, value: V) -> &'a mut V {
+        unsafe {
+            *self.link = Node::new_mut((self.key, value));
+            self.turns.resize(*self.root, true);
+            &mut (**self.link).data.1
+        }
+    }
+}
+
+/// Iterator over the entries of a [`BstMap`], created by [`BstMap::iter`]
+pub struct Iter<'a, K, V>(iter::Iter<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+/// Iterator over the entries of a [`BstMap`] with mutable references to the
+/// values, created by [`BstMap::iter_mut`]
+pub struct IterMut<'a, K, V>(iter::IterMut<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.0.next().map(|(key, value)| (&*key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.0.next_back().map(|(key, value)| (&*key, value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+/// Iterator over the keys of a [`BstMap`], created by [`BstMap::keys`]
+pub struct Keys<'a, K, V>(Iter<'a, K, V>);
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.0.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.0.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+/// Iterator over the values of a [`BstMap`], created by [`BstMap::values`]
+pub struct Values<'a, K, V>(Iter<'a, K, V>);
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.0.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.0.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+/// Iterator over mutable references to the values of a [`BstMap`], created by
+/// [`BstMap::values_mut`]
+pub struct ValuesMut<'a, K, V>(IterMut<'a, K, V>);
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.0.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.0.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+/// Iterator over the entries of a [`BstMap`] whose keys lie within a range,
+/// created by [`BstMap::range`]
+pub struct Range<'a, K, V>(iter::Range<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+/// Iterator over the entries of a [`BstMap`] whose keys lie within a range,
+/// with mutable references to the values, created by [`BstMap::range_mut`]
+pub struct RangeMut<'a, K, V>(iter::RangeMut<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.0.next().map(|(key, value)| (&*key, value))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.0.next_back().map(|(key, value)| (&*key, value))
+    }
+}
+
+/// Owning iterator over the entries of a [`BstMap`], ordered by key, created
+/// by its [`IntoIterator`] implementation
+pub struct IntoIter<K, V>(iter::IntoIter<(K, V)>);
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::{allocations, live_allocations};
+    use crate::xorshift::XorShift;
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+    use std::ops::Bound::{Excluded, Included, Unbounded};
+
+    fn sample_map() -> BstMap<i32, &'static str> {
+        let mut map = BstMap::new(10, "ten");
+        for (key, value) in [(5, "five"), (15, "fifteen"), (1, "one"), (12, "twelve")] {
+            map.insert(key, value);
+        }
+        map
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let mut map = sample_map();
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.get(&5), Some(&"five"));
+        assert_eq!(map.get(&6), None);
+
+        assert_eq!(map.insert(5, "FIVE"), Some("five"));
+        assert_eq!(map.get(&5), Some(&"FIVE"));
+        assert_eq!(map.len(), 5);
+
+        assert!(map.contains_key(&12));
+        assert!(!map.contains_key(&13));
+    }
+
+    #[test]
+    fn get_mut_check() {
+        let mut map = sample_map();
+        *map.get_mut(&15).unwrap() = "quinze";
+        assert_eq!(map.get(&15), Some(&"quinze"));
+        assert_eq!(map.get_mut(&16), None);
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut map = BstMap::new_empty();
+        map.insert(String::from("b"), 2);
+        map.insert(String::from("a"), 1);
+
+        assert_eq!(map.get("a"), Some(&1));
+        assert!(map.contains_key("b"));
+        assert_eq!(map.remove("b"), Some(2));
+        assert_eq!(map.get("b"), None);
+    }
+
+    #[test]
+    fn remove_check() {
+        let mut map = sample_map();
+        assert_eq!(map.remove(&10), Some("ten"));
+        assert_eq!(map.remove(&10), None);
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![1, 5, 12, 15]);
+
+        for key in [1, 5, 12, 15] {
+            map.remove(&key);
+        }
+        assert!(map.is_empty());
+        assert_eq!(map.iter().next(), None);
+    }
+
+    #[test]
+    fn iters_check() {
+        let mut map = sample_map();
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![
+                (&1, &"one"),
+                (&5, &"five"),
+                (&10, &"ten"),
+                (&12, &"twelve"),
+                (&15, &"fifteen")
+            ]
+        );
+        assert_eq!(
+            map.keys().rev().copied().collect::<Vec<_>>(),
+            vec![15, 12, 10, 5, 1]
+        );
+        assert_eq!(map.values().len(), 5);
+
+        for value in map.values_mut() {
+            *value = "changed";
+        }
+        assert!(map.values().all(|&value| value == "changed"));
+
+        for (key, value) in &mut map {
+            if *key > 10 {
+                *value = "big";
+            }
+        }
+        assert_eq!(map.get(&12), Some(&"big"));
+        assert_eq!(map.get(&10), Some(&"changed"));
+
+        let mut keys = vec![];
+        map.in_order_mut(&mut |&key, value| {
+            keys.push(key);
+            if key < 5 {
+                *value = "small";
+            }
+        });
+        assert_eq!(keys, vec![1, 5, 10, 12, 15]);
+        assert_eq!(map.get(&1), Some(&"small"));
+
+        let entries: Vec<(i32, &str)> = map.into_iter().collect();
+        assert_eq!(entries.first(), Some(&(1, "small")));
+        assert_eq!(entries.len(), 5);
+    }
+
+    #[test]
+    fn drop_frees_every_node() {
+        let before = live_allocations();
+        {
+            let mut map = BstMap::new_empty();
+            for key in ["m", "f", "t", "a", "h"] {
+                map.insert(String::from(key), key.repeat(3));
+            }
+            map.remove("f");
+            let mut iter = map.into_iter();
+            iter.next();
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn matches_btreemap() {
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+
+        let mut map = BstMap::new_empty();
+        let mut expected = BTreeMap::new();
+        for step in 0..5_000u64 {
+            let key = rng.next() % 200;
+            match rng.next() % 3 {
+                0 => assert_eq!(map.remove(&key), expected.remove(&key)),
+                _ => assert_eq!(map.insert(key, step), expected.insert(key, step)),
+            }
+            assert_eq!(map.get(&key), expected.get(&key));
+        }
+
+        assert_eq!(map.len(), expected.len());
+        assert!(map.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn entries_track_len() {
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+
+        let mut map = BstMap::new_empty();
+        let mut expected = BTreeMap::new();
+        for _ in 0..5_000u64 {
+            let key = rng.next() % 200;
+            match (map.entry(key), rng.next() % 3) {
+                (Entry::Occupied(entry), 0) => {
+                    assert_eq!(Some(entry.remove()), expected.remove(&key));
+                }
+                (entry, _) => {
+                    *entry.or_insert(0) += 1;
+                    *expected.entry(key).or_insert(0) += 1;
+                }
+            }
+            assert_eq!(map.len(), expected.len());
+        }
+
+        assert!(map.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn entry_counts_words() {
+        let mut counts = BstMap::new_empty();
+        for word in "the cat and the hat and the bat".split(' ') {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(counts.get("the"), Some(&3));
+        assert_eq!(counts.get("and"), Some(&2));
+        assert_eq!(counts.get("cat"), Some(&1));
+        assert_eq!(counts.len(), 5);
+    }
+
+    #[test]
+    fn entry_variants() {
+        let mut map = sample_map();
+
+        match map.entry(5) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &5);
+                assert_eq!(entry.get(), &"five");
+                assert_eq!(entry.insert("FIVE"), "five");
+            }
+            Entry::Vacant(_) => panic!("5 is in the map"),
+        }
+        assert_eq!(map.get(&5), Some(&"FIVE"));
+
+        match map.entry(7) {
+            Entry::Occupied(_) => panic!("7 is not in the map"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &7);
+                assert_eq!(entry.into_key(), 7);
+            }
+        }
+        assert!(!map.contains_key(&7));
+
+        assert_eq!(*map.entry(7).or_insert_with(|| "seven"), "seven");
+        assert_eq!(*map.entry(7).or_insert_with(|| unreachable!()), "seven");
+        assert_eq!(map.len(), 6);
+    }
+
+    #[test]
+    fn entry_and_modify() {
+        let mut map = BstMap::new(String::from("a"), 1);
+        map.entry(String::from("a"))
+            .and_modify(|value| *value += 10)
+            .or_insert(0);
+        map.entry(String::from("b"))
+            .and_modify(|value| *value += 10)
+            .or_insert(0);
+        assert_eq!(map.get("a"), Some(&11));
+        assert_eq!(map.get("b"), Some(&0));
+
+        let value = map
+            .entry(String::from("c"))
+            .or_insert_with_key(|key| key.len());
+        assert_eq!(*value, 1);
+        assert_eq!(*map.entry(String::from("d")).or_default(), 0);
+    }
+
+    #[test]
+    fn entry_remove() {
+        let before = live_allocations();
+        {
+            let mut map = BstMap::new_empty();
+            for key in ["m", "f", "t", "a", "h"] {
+                map.insert(String::from(key), key.len());
+            }
+
+            match map.entry(String::from("m")) {
+                Entry::Occupied(entry) => {
+                    assert_eq!(entry.remove_entry(), (String::from("m"), 1));
+                }
+                Entry::Vacant(_) => panic!("m is in the map"),
+            }
+            if let Entry::Occupied(entry) = map.entry(String::from("a")) {
+                assert_eq!(entry.remove(), 1);
+            }
+
+            assert_eq!(map.len(), 3);
+            assert_eq!(map.keys().collect::<Vec<_>>(), vec!["f", "h", "t"]);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    /// Key that counts how many times it is compared
+    struct Probe<'a>(i32, &'a Cell<usize>);
+
+    impl PartialEq for Probe<'_> {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Probe<'_> {}
+
+    impl PartialOrd for Probe<'_> {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Probe<'_> {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.1.set(self.1.get() + 1);
+            self.0.cmp(&other.0)
+        }
+    }
+
+    #[test]
+    fn entry_searches_once() {
+        let compared = Cell::new(0);
+        let mut map = BstMap::new_empty();
+        for key in [8, 4, 12, 2, 6, 10, 14] {
+            map.insert(Probe(key, &compared), key);
+        }
+
+        // The path to 5 is 8, 4 and 6: three comparisons to find the slot,
+        // and none more to link the node in and grow the sizes
+        compared.set(0);
+        *map.entry(Probe(5, &compared)).or_insert(0) += 5;
+        assert_eq!(compared.get(), 3);
+
+        compared.set(0);
+        match map.entry(Probe(6, &compared)) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 6),
+            Entry::Vacant(_) => panic!("6 is in the map"),
+        }
+        assert_eq!(compared.get(), 3);
+
+        // Updating an occupied entry neither compares again nor allocates
+        compared.set(0);
+        let before = allocations();
+        *map.entry(Probe(10, &compared)).or_insert(0) += 1;
+        map.entry(Probe(5, &compared))
+            .and_modify(|value| *value *= 2);
+        assert_eq!(allocations(), before);
+        assert_eq!(compared.get(), 3 + 3);
+
+        assert_eq!(map.len(), 7);
+        let keys: Vec<_> = map.keys().map(|key| key.0).collect();
+        assert_eq!(keys, vec![2, 4, 5, 8, 10, 12, 14]);
+        let values: Vec<_> = map.values().copied().collect();
+        assert_eq!(values, vec![2, 4, 10, 8, 11, 12, 14]);
+    }
+
+    #[test]
+    fn entry_on_deep_paths() {
+        // Keys added in order make a degenerate tree, deeper than the turns
+        // kept inline by an entry
+        let mut map = BstMap::new_empty();
+        for key in 0..200 {
+            *map.entry(key).or_insert(0) += key;
+        }
+        assert_eq!(map.len(), 200);
+
+        for key in (0..200).rev().step_by(3) {
+            match map.entry(key) {
+                Entry::Occupied(entry) => assert_eq!(entry.remove(), key),
+                Entry::Vacant(_) => panic!("{key} is in the map"),
+            }
+        }
+        *map.entry(1_000).or_default() += 1;
+
+        let expected: Vec<_> = (0..200).filter(|key| (199 - key) % 3 != 0).collect();
+        assert_eq!(map.len(), expected.len() + 1);
+        assert!(map.keys().copied().eq(expected.into_iter().chain([1_000])));
+        assert_eq!(map.iter().next_back(), Some((&1_000, &1)));
+    }
+
+    #[test]
+    fn range_check() {
+        let mut map = BstMap::new_empty();
+        for key in ["kiwi", "apple", "pear", "fig", "banana", "lime"] {
+            map.insert(String::from(key), key.len());
+        }
+
+        let keys: Vec<&String> = map
+            .range::<str, _>((Included("b"), Excluded("l")))
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec!["banana", "fig", "kiwi"]);
+        assert_eq!(map.count_range::<str, _>((Included("lime"), Unbounded)), 2);
+        assert_eq!(map.count_range::<str, _>((Excluded("fig"), Included("pear"))), 3);
+        assert_eq!(map.count_range::<str, _>((Excluded("a"), Excluded("b"))), 1);
+
+        for (_, value) in map.range_mut::<str, _>((Unbounded, Excluded("c"))) {
+            *value = 0;
+        }
+        assert_eq!(map.get("apple"), Some(&0));
+        assert_eq!(map.get("banana"), Some(&0));
+        assert_eq!(map.get("fig"), Some(&3));
+
+        let mut range = map.range_mut::<str, _>((Included("fig"), Included("pear")));
+        assert_eq!(range.next_back().map(|(key, _)| key.as_str()), Some("pear"));
+        assert_eq!(range.next().map(|(key, _)| key.as_str()), Some("fig"));
+    }
+}
@@ -0,0 +1,440 @@
+use std::ptr;
+
+use super::{
+    find, find_link_turns, free_subtree, iter, remove_linked, size, Comparator, NaturalOrder,
+    Node, Turns,
+};
+
+/// Ordered multiset built on the same nodes as
+/// [`BinarySearchTree`](super::BinarySearchTree).
+///
+/// Equal values share a single node that counts how many times they were
+/// added, so the tree only grows with the number of distinct values. Like the
+/// set, it is ordered by [`PartialOrd`] unless another [`Comparator`] is
+/// given.
+///
+/// ### Examples
+/// Here are some examples on how to use this structure
+///
+/// #### Counting values
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BstMultiset;
+///
+/// let mut rolls = BstMultiset::new_empty();
+/// for roll in [3, 1, 3, 6, 3, 1] {
+///     rolls.add(roll);
+/// }
+///
+/// assert_eq!(rolls.count(&3), 3);
+/// assert_eq!(rolls.count(&2), 0);
+/// assert_eq!(rolls.len(), 6);
+/// assert_eq!(rolls.distinct_len(), 3);
+/// ```
+///
+/// #### Removing and iterating
+///
+/// ```
+/// use dsa_abc::binary_search_tree::BstMultiset;
+///
+/// let mut rolls = BstMultiset::new_empty();
+/// for roll in [3, 1, 3, 6, 3, 1] {
+///     rolls.add(roll);
+/// }
+///
+/// rolls.remove_one(&3);
+/// assert_eq!(rolls.remove_all(&1), 2);
+///
+/// // Each value is visited as many times as it was added
+/// assert_eq!(rolls.iter().collect::<Vec<_>>(), vec![&3, &3, &6]);
+/// ```
+///
+pub struct BstMultiset<T, C = NaturalOrder> {
+    root: *mut Node<(T, usize)>,
+    len: usize,
+    comparator: C,
+}
+
+impl<T: PartialOrd> BstMultiset<T> {
+    /// Creates a new empty multiset
+    pub fn new_empty() -> BstMultiset<T> {
+        Self::with_comparator(NaturalOrder)
+    }
+}
+
+impl<T, C: Comparator<T>> BstMultiset<T, C> {
+    /// Creates a new empty multiset ordered by `comparator` instead of
+    /// [`PartialOrd`]
+    pub fn with_comparator(comparator: C) -> BstMultiset<T, C> {
+        BstMultiset {
+            root: ptr::null_mut(),
+            len: 0,
+            comparator,
+        }
+    }
+
+    /// Returns the number of values in the multiset, counting every copy.
+    /// O(1) time complexity
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the number of distinct values in the multiset.
+    /// O(1) time complexity
+    pub fn distinct_len(&self) -> usize {
+        unsafe { size(self.root) }
+    }
+
+    /// Returns `true` if the multiset holds no values. O(1) time complexity
+    pub fn is_empty(&self) -> bool {
+        self.root.is_null()
+    }
+
+    /// Adds a copy of `value`. If an equal value is already stored its count
+    /// is incremented and `value` is dropped. A value the comparator can't
+    /// order against the stored ones, like NaN with [`NaturalOrder`], isn't
+    /// added. O(log n) time complexity
+    pub fn add(&mut self, value: T) {
+        let mut turns = Turns::new();
+        unsafe {
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |entry| {
+                self.comparator.partial_compare(&value, &entry.0)
+            });
+            if link.is_null() {
+                return;
+            }
+            if (*link).is_null() {
+                *link = Node::new_mut((value, 1));
+                turns.resize(self.root, true);
+            } else {
+                (**link).data.1 += 1;
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Returns how many copies of `value` are stored. O(log n) time complexity
+    pub fn count(&self, value: &T) -> usize {
+        let cmp = |entry: &(T, usize)| self.comparator.partial_compare(value, &entry.0);
+        unsafe { find(self.root, cmp).as_ref().map_or(0, |node| node.data.1) }
+    }
+
+    /// Returns `true` if at least one copy of `value` is stored.
+    /// O(log n) time complexity
+    pub fn contains(&self, value: &T) -> bool {
+        self.count(value) > 0
+    }
+
+    /// Removes a single copy of `value`, returning `true` if there was one.
+    /// The node is only unlinked once its last copy is removed.
+    /// O(log n) time complexity
+    pub fn remove_one(&mut self, value: &T) -> bool {
+        let mut turns = Turns::new();
+        unsafe {
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |entry| {
+                self.comparator.partial_compare(value, &entry.0)
+            });
+            if link.is_null() || (*link).is_null() {
+                return false;
+            }
+
+            if (**link).data.1 > 1 {
+                (**link).data.1 -= 1;
+            } else {
+                turns.resize(self.root, false);
+                remove_linked(link);
+            }
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes every copy of `value` and returns how many there were.
+    /// O(log n) time complexity
+    pub fn remove_all(&mut self, value: &T) -> usize {
+        let mut turns = Turns::new();
+        let count = unsafe {
+            let link = find_link_turns(ptr::addr_of_mut!(self.root), &mut turns, |entry| {
+                self.comparator.partial_compare(value, &entry.0)
+            });
+            if link.is_null() || (*link).is_null() {
+                return 0;
+            }
+
+            turns.resize(self.root, false);
+            remove_linked(link).1
+        };
+        self.len -= count;
+        count
+    }
+
+    /// Returns an iterator over the values in order, yielding each one as many
+    /// times as it was added
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            nodes: iter::Iter::new(self.root, self.distinct_len()),
+            front: None,
+            back: None,
+            remaining: self.len,
+        }
+    }
+
+    /// Returns an iterator over the distinct values in order, each paired with
+    /// its count
+    pub fn counts(&self) -> Counts<'_, T> {
+        Counts(iter::Iter::new(self.root, self.distinct_len()))
+    }
+}
+
+impl<T, C> Drop for BstMultiset<T, C> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.root);
+        }
+        self.root = ptr::null_mut();
+        self.len = 0;
+    }
+}
+
+impl<'a, T, C: Comparator<T>> IntoIterator for &'a BstMultiset<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Iterator over the values of a [`BstMultiset`], created by
+/// [`BstMultiset::iter`]. Each value is repeated as many times as it was
+/// added.
+///
+/// Walks the nodes like the set's iterator and keeps, for each end, the node
+/// being repeated and how many copies of it are left.
+pub struct Iter<'a, T> {
+    nodes: iter::Iter<'a, (T, usize)>,
+    front: Option<(&'a T, usize)>,
+    back: Option<(&'a T, usize)>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        loop {
+            if let Some((value, copies)) = &mut self.front {
+                if *copies > 0 {
+                    *copies -= 1;
+                    self.remaining -= 1;
+                    return Some(*value);
+                }
+            }
+
+            // Once the nodes run out, the rest of the copies are at the back
+            self.front = match self.nodes.next() {
+                Some((value, count)) => Some((value, *count)),
+                None => self.back.take(),
+            };
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        loop {
+            if let Some((value, copies)) = &mut self.back {
+                if *copies > 0 {
+                    *copies -= 1;
+                    self.remaining -= 1;
+                    return Some(*value);
+                }
+            }
+
+            self.back = match self.nodes.next_back() {
+                Some((value, count)) => Some((value, *count)),
+                None => self.front.take(),
+            };
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+/// Iterator over the distinct values of a [`BstMultiset`] and their counts,
+/// created by [`BstMultiset::counts`]
+pub struct Counts<'a, T>(iter::Iter<'a, (T, usize)>);
+
+impl<'a, T> Iterator for Counts<'a, T> {
+    type Item = (&'a T, usize);
+
+    fn next(&mut self) -> Option<(&'a T, usize)> {
+        self.0.next().map(|(value, count)| (value, *count))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Counts<'a, T> {
+    fn next_back(&mut self) -> Option<(&'a T, usize)> {
+        self.0.next_back().map(|(value, count)| (value, *count))
+    }
+}
+
+impl<T> ExactSizeIterator for Counts<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::live_allocations;
+    use crate::binary_search_tree::ReverseOrder;
+    use crate::xorshift::XorShift;
+    use std::collections::BTreeMap;
+
+    fn sample_multiset() -> BstMultiset<i32> {
+        let mut multiset = BstMultiset::new_empty();
+        for value in [5, 3, 8, 3, 5, 5, 1] {
+            multiset.add(value);
+        }
+        multiset
+    }
+
+    #[test]
+    fn add_and_count() {
+        let multiset = sample_multiset();
+        assert_eq!(multiset.len(), 7);
+        assert_eq!(multiset.distinct_len(), 4);
+        assert_eq!(multiset.count(&5), 3);
+        assert_eq!(multiset.count(&3), 2);
+        assert_eq!(multiset.count(&4), 0);
+        assert!(multiset.contains(&8));
+        assert!(!multiset.contains(&9));
+    }
+
+    #[test]
+    fn remove_one_and_all() {
+        let mut multiset = sample_multiset();
+        assert!(multiset.remove_one(&5));
+        assert_eq!(multiset.count(&5), 2);
+        assert_eq!(multiset.distinct_len(), 4);
+
+        assert!(multiset.remove_one(&8));
+        assert!(!multiset.remove_one(&8));
+        assert_eq!(multiset.distinct_len(), 3);
+
+        assert_eq!(multiset.remove_all(&5), 2);
+        assert_eq!(multiset.remove_all(&5), 0);
+        assert_eq!(multiset.len(), 3);
+        assert_eq!(multiset.iter().collect::<Vec<_>>(), vec![&1, &3, &3]);
+    }
+
+    #[test]
+    fn iter_repeats_values() {
+        let multiset = sample_multiset();
+        let values: Vec<_> = multiset.iter().copied().collect();
+        assert_eq!(values, vec![1, 3, 3, 5, 5, 5, 8]);
+        assert_eq!(multiset.iter().len(), 7);
+
+        let reversed: Vec<_> = multiset.iter().rev().copied().collect();
+        assert_eq!(reversed, vec![8, 5, 5, 5, 3, 3, 1]);
+
+        let counts: Vec<_> = multiset.counts().collect();
+        assert_eq!(counts, vec![(&1, 1), (&3, 2), (&5, 3), (&8, 1)]);
+    }
+
+    #[test]
+    fn iter_both_ends_share_a_node() {
+        let mut multiset = BstMultiset::new_empty();
+        for _ in 0..5 {
+            multiset.add('x');
+        }
+        multiset.add('a');
+
+        let mut iter = multiset.iter();
+        assert_eq!(iter.next(), Some(&'a'));
+        assert_eq!(iter.next_back(), Some(&'x'));
+        assert_eq!(iter.next(), Some(&'x'));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some(&'x'));
+        assert_eq!(iter.next(), Some(&'x'));
+        assert_eq!(iter.next(), Some(&'x'));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn custom_comparator() {
+        let mut multiset = BstMultiset::with_comparator(ReverseOrder);
+        for value in [1, 2, 2, 3] {
+            multiset.add(value);
+        }
+        assert_eq!(multiset.iter().collect::<Vec<_>>(), vec![&3, &2, &2, &1]);
+    }
+
+    #[test]
+    fn nan_is_never_counted() {
+        let mut multiset = BstMultiset::new_empty();
+        multiset.add(1.0);
+        multiset.add(f64::NAN);
+        assert_eq!(multiset.len(), 1);
+        assert_eq!(multiset.count(&f64::NAN), 0);
+        assert!(!multiset.remove_one(&f64::NAN));
+        assert_eq!(multiset.remove_all(&f64::NAN), 0);
+        assert_eq!(multiset.count(&1.0), 1);
+    }
+
+    #[test]
+    fn matches_btreemap_counts() {
+        let before = live_allocations();
+        {
+            let mut rng = XorShift(0x6A09_E667_F3BC_C909);
+
+            let mut multiset = BstMultiset::new_empty();
+            let mut expected: BTreeMap<u64, usize> = BTreeMap::new();
+            for _ in 0..5_000 {
+                let value = rng.next() % 50;
+                match rng.next() % 4 {
+                    0 => {
+                        let removed = multiset.remove_one(&value);
+                        assert_eq!(removed, expected.contains_key(&value));
+                        if let Some(count) = expected.get_mut(&value) {
+                            *count -= 1;
+                            if *count == 0 {
+                                expected.remove(&value);
+                            }
+                        }
+                    }
+                    1 if value.is_multiple_of(5) => {
+                        let removed = expected.remove(&value).unwrap_or(0);
+                        assert_eq!(multiset.remove_all(&value), removed);
+                    }
+                    _ => {
+                        multiset.add(value);
+                        *expected.entry(value).or_insert(0) += 1;
+                    }
+                }
+                let count = expected.get(&value).copied().unwrap_or(0);
+                assert_eq!(multiset.count(&value), count);
+            }
+
+            assert_eq!(multiset.distinct_len(), expected.len());
+            assert_eq!(multiset.len(), expected.values().sum::<usize>());
+            assert!(multiset.counts().eq(expected.iter().map(|(v, c)| (v, *c))));
+        }
+        assert_eq!(live_allocations(), before);
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use super::Node;
+
+/// Read-only handle to a node of a [`BinarySearchTree`](super::BinarySearchTree),
+/// for walking the shape of the tree without unsafe code. Created by
+/// [`BinarySearchTree::root`](super::BinarySearchTree::root).
+///
+/// A handle borrows the tree it came from, so the tree can't be changed while
+/// any handle is alive:
+///
+/// ```compile_fail
+/// use dsa_abc::binary_search_tree::BinarySearchTree;
+///
+/// let mut tree = BinarySearchTree::new(2);
+/// let root = tree.root().unwrap();
+/// tree.add(1);
+/// println!("{}", root.value());
+/// ```
+///
+/// ```
+/// use dsa_abc::binary_search_tree::{BinarySearchTree, NodeRef};
+///
+/// let tree = BinarySearchTree::from_sorted(1..=7);
+///
+/// // Counts the leaves by walking the nodes
+/// fn leaves(node: Option<NodeRef<'_, i32>>) -> usize {
+///     match node {
+///         None => 0,
+///         Some(node) if node.is_leaf() => 1,
+///         Some(node) => leaves(node.left()) + leaves(node.right()),
+///     }
+/// }
+///
+/// let root = tree.root().unwrap();
+/// assert_eq!(root.value(), &4);
+/// assert_eq!(root.left().map(|left| *left.value()), Some(2));
+/// assert_eq!(leaves(tree.root()), 4);
+/// ```
+pub struct NodeRef<'a, T> {
+    node: &'a Node<T>,
+}
+
+impl<'a, T> NodeRef<'a, T> {
+    /// Wraps the node `node` points to, or returns `None` if it is null
+    pub(super) fn new(node: *mut Node<T>) -> Option<NodeRef<'a, T>> {
+        unsafe { node.as_ref() }.map(|node| NodeRef { node })
+    }
+
+    /// Returns the value stored in the node
+    pub fn value(&self) -> &'a T {
+        &self.node.data
+    }
+
+    /// Returns the left child, holding the smaller values, if any
+    pub fn left(&self) -> Option<NodeRef<'a, T>> {
+        NodeRef::new(self.node.left)
+    }
+
+    /// Returns the right child, holding the greater values, if any
+    pub fn right(&self) -> Option<NodeRef<'a, T>> {
+        NodeRef::new(self.node.right)
+    }
+
+    /// Returns `true` if the node has no children
+    pub fn is_leaf(&self) -> bool {
+        self.node.left.is_null() && self.node.right.is_null()
+    }
+
+    /// Returns the number of nodes in the subtree rooted at this node, itself
+    /// included. O(1) time complexity
+    pub fn subtree_len(&self) -> usize {
+        self.node.size
+    }
+}
+
+impl<T> Clone for NodeRef<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodeRef<'_, T> {}
+
+impl<T: fmt::Debug> fmt::Debug for NodeRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeRef")
+            .field("value", self.value())
+            .field("left", &self.left().map(|left| left.value()))
+            .field("right", &self.right().map(|right| right.value()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::binary_search_tree::BinarySearchTree;
+
+    #[test]
+    fn walks_the_shape() {
+        let tree: BinarySearchTree<i32> = [10, 5, 15, 1, 9, 11, 30].into_iter().collect();
+        let root = tree.root().unwrap();
+        assert_eq!(root.value(), &10);
+        assert_eq!(root.subtree_len(), 7);
+        assert!(!root.is_leaf());
+
+        let left = root.left().unwrap();
+        assert_eq!(left.value(), &5);
+        assert_eq!(left.subtree_len(), 3);
+
+        let leaf = left.right().unwrap();
+        assert_eq!(leaf.value(), &9);
+        assert!(leaf.is_leaf());
+        assert!(leaf.left().is_none() && leaf.right().is_none());
+
+        assert_eq!(
+            format!("{root:?}"),
+            "NodeRef { value: 10, left: Some(5), right: Some(15) }"
+        );
+
+        let empty = BinarySearchTree::<i32>::new_empty();
+        assert!(empty.root().is_none());
+    }
+}
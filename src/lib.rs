@@ -1,2 +1,12 @@
+pub mod avl_tree;
 pub mod binary_search_tree;
-pub mod singly_linked_list;
\ No newline at end of file
+pub mod red_black_tree;
+pub mod singly_linked_list;
+
+mod raw_node;
+
+#[cfg(test)]
+mod alloc_counter;
+
+#[cfg(test)]
+mod xorshift;
@@ -0,0 +1,30 @@
+/// Node of a tree linked through raw pointers to its children, so the trees
+/// of this crate can share the unsafe code that only follows child links
+pub(crate) trait RawNode: Sized {
+    /// Returns the links to the left and right children
+    fn children(&mut self) -> (&mut *mut Self, &mut *mut Self);
+}
+
+/// Frees every node of the subtree rooted at `node`, which may be null,
+/// without recursion, so degenerate trees can't overflow the stack. Left
+/// children are rotated up until the current node has none, then it is freed
+/// and the walk continues through its right child. Any other links, like
+/// parent pointers, are ignored. O(n) time complexity, O(1) space complexity
+pub(crate) unsafe fn free_subtree<N: RawNode>(mut node: *mut N) {
+    unsafe {
+        while !node.is_null() {
+            let (left, right) = (*node).children();
+            if left.is_null() {
+                let right = *right;
+                drop(Box::from_raw(node));
+                node = right;
+            } else {
+                let child = *left;
+                let (_, child_right) = (*child).children();
+                *left = *child_right;
+                *child_right = node;
+                node = child;
+            }
+        }
+    }
+}
@@ -0,0 +1,1057 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ptr;
+
+use crate::raw_node::{free_subtree, RawNode};
+
+pub mod map;
+
+pub use map::RedBlackMap;
+
+/// Self-balancing Binary Search Tree that colors every node red or black.
+///
+/// The coloring rules (the root is black, a red node never has a red child
+/// and every path from a node down to its leaves crosses the same number of
+/// black nodes) keep the longest path at most twice as long as the shortest
+/// one, so search, insert and deletion are always O(log n). Compared to an
+/// [`AvlTree`](crate::avl_tree::AvlTree) it is less strictly balanced, but
+/// needs at most three rotations to fix the tree after any mutation.
+///
+/// Method names match [`BinarySearchTree`](crate::binary_search_tree::BinarySearchTree),
+/// so both can be swapped behind a type alias.
+///
+/// This implementation utilizes unsafe rust in some places due to the
+/// complexity and runtime overhead of building a compile-time safe structure.
+///
+/// ### Examples
+/// Here are some examples on how to use this structure
+///
+/// #### Inserting an element
+///
+/// ```
+/// use dsa_abc::red_black_tree::RedBlackTree;
+///
+/// // Creates the tree with an initial value of 10
+/// let mut tree = RedBlackTree::new(10);
+/// // Adds a new value following BST's ordering rules
+/// tree.add(5);
+/// assert_eq!(tree.get(&5), Some(&5));
+/// ```
+///
+/// #### Deleting an element
+///
+/// ```
+/// use dsa_abc::red_black_tree::RedBlackTree;
+///
+/// let mut tree = RedBlackTree::new(10);
+/// tree.add(5);
+/// assert_eq!(tree.get(&5), Some(&5));
+///
+/// // deletes the node that contains a value that is equal to 5
+/// tree.delete(&5);
+/// assert_eq!(tree.get(&5), None);
+/// ```
+///
+/// #### Swapping implementations
+///
+/// ```
+/// use dsa_abc::red_black_tree::RedBlackTree;
+///
+/// // Could be `BinarySearchTree<i32>` as well
+/// type Tree = RedBlackTree<i32>;
+///
+/// let mut tree = Tree::new(10);
+/// tree.add(5);
+/// tree.add(15);
+///
+/// let mut vals = vec![];
+/// tree.in_order(&mut |&data| vals.push(data));
+/// assert_eq!(vals, vec![5, 10, 15]);
+/// ```
+///
+pub struct RedBlackTree<T: PartialOrd> {
+    root: *mut Node<T>,
+    len: usize,
+}
+
+impl<T: PartialOrd> RedBlackTree<T> {
+    /// Create a new red-black tree with an initial data as root
+    pub fn new(data: T) -> RedBlackTree<T> {
+        let root = Node::new_mut(data, ptr::null_mut());
+        unsafe {
+            (*root).color = Color::Black;
+        }
+
+        RedBlackTree { root, len: 1 }
+    }
+
+    /// Returns the number of elements in the tree. O(1) time complexity
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree holds no elements. O(1) time complexity
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Compares `data` with a node's data. `None` means the values can't be
+    /// ordered, like `NaN` for floats: such data is never added, found or
+    /// deleted
+    fn compare(data: &T, node: &T) -> Option<Ordering> {
+        data.partial_cmp(node)
+    }
+
+    /// Add a node to the tree using `data`. If data already exists in tree,
+    /// ignore. O(log n) time complexity, O(1) space complexity
+    pub fn add(&mut self, data: T) {
+        unsafe {
+            let found = search(self.root, |node| Self::compare(&data, node));
+            if let Some(Err((parent, side))) = found {
+                insert_at(ptr::addr_of_mut!(self.root), parent, side, data);
+                self.len += 1;
+            }
+        }
+    }
+
+    /// Get a node value for `data` if a node exists with this data. Primarily
+    /// used to check if a given data is present in the tree. O(log n) time
+    /// complexity, O(1) space complexity
+    pub fn get(&self, data: &T) -> Option<&T> {
+        unsafe {
+            search(self.root, |node| Self::compare(data, node))
+                .and_then(Result::ok)
+                .map(|node| &(*node).data)
+        }
+    }
+
+    /// Deletes the node that holds `data`, if any. O(log n) time complexity,
+    /// O(1) space complexity
+    pub fn delete(&mut self, data: &T) {
+        unsafe {
+            if let Some(Ok(node)) = search(self.root, |node| Self::compare(data, node)) {
+                remove_node(ptr::addr_of_mut!(self.root), node);
+                self.len -= 1;
+            }
+        }
+    }
+
+    /// In order traversal with `on_find` callback when each node is found.
+    /// The references passed to `on_find` borrow the tree, so they can't
+    /// outlive it:
+    ///
+    /// ```compile_fail
+    /// use dsa_abc::red_black_tree::RedBlackTree;
+    ///
+    /// let mut values = vec![];
+    /// {
+    ///     let tree = RedBlackTree::new(1);
+    ///     tree.in_order(&mut |data| values.push(data));
+    /// }
+    /// println!("{values:?}");
+    /// ```
+    pub fn in_order<'a, F>(&'a self, on_find: &mut F)
+    where
+        F: FnMut(&'a T),
+    {
+        for data in Iter::new(self.root, self.len) {
+            on_find(data);
+        }
+    }
+
+    /// Pre order traversal with `on_find` callback when each node is found.
+    /// Walks the parent links instead of recursing. O(1) space complexity
+    pub fn pre_order<'a, F>(&'a self, on_find: &mut F)
+    where
+        F: FnMut(&'a T),
+    {
+        let mut node = self.root;
+        while !node.is_null() {
+            unsafe {
+                on_find(&(*node).data);
+                node = pre_order_successor(node);
+            }
+        }
+    }
+
+    /// Post order traversal with `on_find` callback when each node is found.
+    /// Walks the parent links instead of recursing. O(1) space complexity
+    pub fn post_order<'a, F>(&'a self, on_find: &mut F)
+    where
+        F: FnMut(&'a T),
+    {
+        let mut node = unsafe { post_order_first(self.root) };
+        while !node.is_null() {
+            unsafe {
+                on_find(&(*node).data);
+                node = post_order_successor(node);
+            }
+        }
+    }
+
+    /// Returns an iterator over the tree's values in order. Iterating from
+    /// both ends is supported. O(1) space complexity
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self.root, self.len)
+    }
+
+    /// Returns the number of levels in the tree, 0 when it is empty. The
+    /// coloring rules keep it at most 2 log2(n + 1).
+    /// O(n) time complexity, O(log n) space complexity
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut pending = vec![(self.root, 1)];
+        while let Some((node, depth)) = pending.pop() {
+            if let Some(node) = unsafe { node.as_ref() } {
+                height = height.max(depth);
+                pending.push((node.left, depth + 1));
+                pending.push((node.right, depth + 1));
+            }
+        }
+        height
+    }
+
+    /// Panics if any red-black invariant is broken: the ordering of the
+    /// values, the parent links, a black root, no red node with a red child,
+    /// the same black height on every path, or the tracked length.
+    /// Meant for tests and debugging. O(n) time complexity
+    pub fn assert_invariants(&self) {
+        unsafe { assert_invariants(self.root, self.len, |a, b| a < b) }
+    }
+}
+
+impl<T: PartialOrd> Drop for RedBlackTree<T> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.root);
+        }
+        self.root = ptr::null_mut();
+        self.len = 0;
+    }
+}
+
+impl<T: PartialOrd> IntoIterator for RedBlackTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    /// Consumes the tree into an iterator over its values in order
+    fn into_iter(mut self) -> IntoIter<T> {
+        let root = self.root;
+        let len = self.len;
+        self.root = ptr::null_mut();
+        self.len = 0;
+        IntoIter::new(root, len)
+    }
+}
+
+impl<'a, T: PartialOrd> IntoIterator for &'a RedBlackTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// In order iterator over the values of a [`RedBlackTree`], created by
+/// [`RedBlackTree::iter`].
+///
+/// Nodes know their parents, so the next value at each end is found by
+/// walking the tree instead of keeping a stack.
+pub struct Iter<'a, T> {
+    front: *mut Node<T>,
+    back: *mut Node<T>,
+    remaining: usize,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn new(root: *mut Node<T>, len: usize) -> Iter<'a, T> {
+        unsafe {
+            Iter {
+                front: minimum(root),
+                back: maximum(root),
+                remaining: len,
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        unsafe {
+            let node = self.front;
+            self.front = successor(node);
+            self.remaining -= 1;
+            Some(&(*node).data)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        unsafe {
+            let node = self.back;
+            self.back = predecessor(node);
+            self.remaining -= 1;
+            Some(&(*node).data)
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+/// Owning in order iterator over the values of a [`RedBlackTree`], created by
+/// its [`IntoIterator`] implementation. Nodes are freed as they are yielded
+pub struct IntoIter<T> {
+    current: *mut Node<T>,
+    remaining: usize,
+    marker: PhantomData<Box<Node<T>>>,
+}
+
+impl<T> IntoIter<T> {
+    fn new(root: *mut Node<T>, len: usize) -> IntoIter<T> {
+        IntoIter {
+            current: root,
+            remaining: len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // Same left rotations as `free_subtree`, parent links are ignored
+        // since the nodes are freed anyway
+        unsafe {
+            while !self.current.is_null() {
+                let left = (*self.current).left;
+                if left.is_null() {
+                    let node = Box::from_raw(self.current);
+                    self.current = node.right;
+                    self.remaining -= 1;
+                    return Some(node.data);
+                }
+
+                (*self.current).left = (*left).right;
+                (*left).right = self.current;
+                self.current = left;
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Color {
+    Red,
+    Black,
+}
+
+struct Node<T> {
+    data: T,
+    left: *mut Node<T>,
+    right: *mut Node<T>,
+    parent: *mut Node<T>,
+    color: Color,
+}
+
+impl<T> Node<T> {
+    fn new(data: T, parent: *mut Node<T>) -> Node<T> {
+        Node {
+            data,
+            left: ptr::null_mut(),
+            right: ptr::null_mut(),
+            parent,
+            color: Color::Red,
+        }
+    }
+
+    fn new_mut(data: T, parent: *mut Node<T>) -> *mut Node<T> {
+        Box::into_raw(Box::new(Self::new(data, parent)))
+    }
+}
+
+impl<T> RawNode for Node<T> {
+    fn children(&mut self) -> (&mut *mut Node<T>, &mut *mut Node<T>) {
+        (&mut self.left, &mut self.right)
+    }
+}
+
+/// Null nodes count as black leaves
+unsafe fn is_red<T>(node: *mut Node<T>) -> bool {
+    unsafe { !node.is_null() && (*node).color == Color::Red }
+}
+
+/// Leftmost node of the subtree rooted at `node`, or null if it is empty
+unsafe fn minimum<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        while !node.is_null() && !(*node).left.is_null() {
+            node = (*node).left;
+        }
+    }
+    node
+}
+
+/// Rightmost node of the subtree rooted at `node`, or null if it is empty
+unsafe fn maximum<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        while !node.is_null() && !(*node).right.is_null() {
+            node = (*node).right;
+        }
+    }
+    node
+}
+
+/// Next node in order, found through the right subtree or the parent links
+unsafe fn successor<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        if !(*node).right.is_null() {
+            return minimum((*node).right);
+        }
+
+        let mut parent = (*node).parent;
+        while !parent.is_null() && node == (*parent).right {
+            node = parent;
+            parent = (*parent).parent;
+        }
+        parent
+    }
+}
+
+/// Previous node in order, found through the left subtree or the parent links
+unsafe fn predecessor<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        if !(*node).left.is_null() {
+            return maximum((*node).left);
+        }
+
+        let mut parent = (*node).parent;
+        while !parent.is_null() && node == (*parent).left {
+            node = parent;
+            parent = (*parent).parent;
+        }
+        parent
+    }
+}
+
+/// Next node in pre order: a child if there is one, or else the right child
+/// of the closest ancestor whose right subtree hasn't been visited yet
+unsafe fn pre_order_successor<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        if !(*node).left.is_null() {
+            return (*node).left;
+        }
+        if !(*node).right.is_null() {
+            return (*node).right;
+        }
+
+        let mut parent = (*node).parent;
+        while !parent.is_null() && (node == (*parent).right || (*parent).right.is_null()) {
+            node = parent;
+            parent = (*parent).parent;
+        }
+        parent.as_ref().map_or(ptr::null_mut(), |parent| parent.right)
+    }
+}
+
+/// First node in post order of the subtree rooted at `node`, reached by going
+/// left whenever possible and right otherwise, or null if it is empty
+unsafe fn post_order_first<T>(mut node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        while !node.is_null() {
+            let next = if (*node).left.is_null() {
+                (*node).right
+            } else {
+                (*node).left
+            };
+            if next.is_null() {
+                break;
+            }
+            node = next;
+        }
+    }
+    node
+}
+
+/// Next node in post order: the first one of the right sibling's subtree when
+/// coming up from a left child, or else the parent
+unsafe fn post_order_successor<T>(node: *mut Node<T>) -> *mut Node<T> {
+    unsafe {
+        let parent = (*node).parent;
+        if !parent.is_null() && node == (*parent).left && !(*parent).right.is_null() {
+            return post_order_first((*parent).right);
+        }
+        parent
+    }
+}
+
+/// Outcome of a [`search`] that could place the value: the matching node, or
+/// the parent under which the value belongs together with the side it goes to
+type Found<T> = Result<*mut Node<T>, (*mut Node<T>, Ordering)>;
+
+/// Walks down from `node` following `cmp`, which compares the searched value
+/// with each node's data. Returns `None` as soon as `cmp` can't order the
+/// value against a node, as it then has neither a match nor a place in the
+/// tree
+unsafe fn search<T, F>(mut node: *mut Node<T>, mut cmp: F) -> Option<Found<T>>
+where
+    F: FnMut(&T) -> Option<Ordering>,
+{
+    let mut parent = ptr::null_mut();
+    let mut side = Ordering::Equal;
+
+    unsafe {
+        while !node.is_null() {
+            side = cmp(&(*node).data)?;
+            parent = node;
+            node = match side {
+                Ordering::Greater => (*node).right,
+                Ordering::Less => (*node).left,
+                Ordering::Equal => return Some(Ok(node)),
+            };
+        }
+    }
+
+    Some(Err((parent, side)))
+}
+
+/// Points whatever linked to `old` (its parent or the root) at `new`
+unsafe fn replace_child<T>(root: *mut *mut Node<T>, old: *mut Node<T>, new: *mut Node<T>) {
+    unsafe {
+        let parent = (*old).parent;
+        if parent.is_null() {
+            *root = new;
+        } else if (*parent).left == old {
+            (*parent).left = new;
+        } else {
+            (*parent).right = new;
+        }
+
+        if !new.is_null() {
+            (*new).parent = parent;
+        }
+    }
+}
+
+/// Rotates the subtree rooted at `node` to the left
+unsafe fn rotate_left<T>(root: *mut *mut Node<T>, node: *mut Node<T>) {
+    unsafe {
+        let right = (*node).right;
+        (*node).right = (*right).left;
+        if !(*right).left.is_null() {
+            (*(*right).left).parent = node;
+        }
+
+        replace_child(root, node, right);
+        (*right).left = node;
+        (*node).parent = right;
+    }
+}
+
+/// Rotates the subtree rooted at `node` to the right
+unsafe fn rotate_right<T>(root: *mut *mut Node<T>, node: *mut Node<T>) {
+    unsafe {
+        let left = (*node).left;
+        (*node).left = (*left).right;
+        if !(*left).right.is_null() {
+            (*(*left).right).parent = node;
+        }
+
+        replace_child(root, node, left);
+        (*left).right = node;
+        (*node).parent = left;
+    }
+}
+
+/// Links a new red node holding `data` under `parent` on the given `side`,
+/// as found by [`search`], and restores the red-black rules. Returns the new
+/// node
+unsafe fn insert_at<T>(
+    root: *mut *mut Node<T>,
+    parent: *mut Node<T>,
+    side: Ordering,
+    data: T,
+) -> *mut Node<T> {
+    let node = Node::new_mut(data, parent);
+
+    unsafe {
+        if parent.is_null() {
+            *root = node;
+        } else if side == Ordering::Less {
+            (*parent).left = node;
+        } else {
+            (*parent).right = node;
+        }
+
+        insert_fixup(root, node);
+    }
+
+    node
+}
+
+/// Fixes red-red violations going up from the freshly inserted `node`,
+/// recoloring while its uncle is red and rotating once it is black
+unsafe fn insert_fixup<T>(root: *mut *mut Node<T>, mut node: *mut Node<T>) {
+    unsafe {
+        while is_red((*node).parent) {
+            let mut parent = (*node).parent;
+            // A red parent is never the root, so the grandparent exists
+            let grandparent = (*parent).parent;
+
+            if parent == (*grandparent).left {
+                let uncle = (*grandparent).right;
+                if is_red(uncle) {
+                    (*parent).color = Color::Black;
+                    (*uncle).color = Color::Black;
+                    (*grandparent).color = Color::Red;
+                    node = grandparent;
+                    continue;
+                }
+
+                if node == (*parent).right {
+                    rotate_left(root, parent);
+                    node = parent;
+                    parent = (*node).parent;
+                }
+                (*parent).color = Color::Black;
+                (*grandparent).color = Color::Red;
+                rotate_right(root, grandparent);
+            } else {
+                let uncle = (*grandparent).left;
+                if is_red(uncle) {
+                    (*parent).color = Color::Black;
+                    (*uncle).color = Color::Black;
+                    (*grandparent).color = Color::Red;
+                    node = grandparent;
+                    continue;
+                }
+
+                if node == (*parent).left {
+                    rotate_right(root, parent);
+                    node = parent;
+                    parent = (*node).parent;
+                }
+                (*parent).color = Color::Black;
+                (*grandparent).color = Color::Red;
+                rotate_left(root, grandparent);
+            }
+        }
+
+        (**root).color = Color::Black;
+    }
+}
+
+/// Unlinks `node` from the tree, restores the red-black rules, frees it and
+/// returns its data. When the node has two children its successor is moved
+/// into its place and takes over its color
+unsafe fn remove_node<T>(root: *mut *mut Node<T>, node: *mut Node<T>) -> T {
+    unsafe {
+        let mut removed_color = (*node).color;
+        // Node that moves into the removed position and its parent, kept apart
+        // since the moved node may be a null leaf
+        let child;
+        let child_parent;
+
+        if (*node).left.is_null() {
+            child = (*node).right;
+            child_parent = (*node).parent;
+            replace_child(root, node, child);
+        } else if (*node).right.is_null() {
+            child = (*node).left;
+            child_parent = (*node).parent;
+            replace_child(root, node, child);
+        } else {
+            let successor = minimum((*node).right);
+            removed_color = (*successor).color;
+            child = (*successor).right;
+
+            if (*successor).parent == node {
+                child_parent = successor;
+            } else {
+                child_parent = (*successor).parent;
+                replace_child(root, successor, child);
+                (*successor).right = (*node).right;
+                (*(*successor).right).parent = successor;
+            }
+
+            replace_child(root, node, successor);
+            (*successor).left = (*node).left;
+            (*(*successor).left).parent = successor;
+            (*successor).color = (*node).color;
+        }
+
+        if removed_color == Color::Black {
+            remove_fixup(root, child, child_parent);
+        }
+
+        Box::from_raw(node).data
+    }
+}
+
+/// Restores the black height after a black node was removed. `node` carries
+/// an extra black, which is pushed up or absorbed through recoloring and
+/// rotations around its sibling
+unsafe fn remove_fixup<T>(
+    root: *mut *mut Node<T>,
+    mut node: *mut Node<T>,
+    mut parent: *mut Node<T>,
+) {
+    unsafe {
+        while node != *root && !is_red(node) {
+            // The sibling always exists, since the path through `node` is
+            // one black short
+            if node == (*parent).left {
+                let mut sibling = (*parent).right;
+                if is_red(sibling) {
+                    (*sibling).color = Color::Black;
+                    (*parent).color = Color::Red;
+                    rotate_left(root, parent);
+                    sibling = (*parent).right;
+                }
+
+                if !is_red((*sibling).left) && !is_red((*sibling).right) {
+                    (*sibling).color = Color::Red;
+                    node = parent;
+                    parent = (*node).parent;
+                    continue;
+                }
+
+                if !is_red((*sibling).right) {
+                    (*(*sibling).left).color = Color::Black;
+                    (*sibling).color = Color::Red;
+                    rotate_right(root, sibling);
+                    sibling = (*parent).right;
+                }
+                (*sibling).color = (*parent).color;
+                (*parent).color = Color::Black;
+                (*(*sibling).right).color = Color::Black;
+                rotate_left(root, parent);
+            } else {
+                let mut sibling = (*parent).left;
+                if is_red(sibling) {
+                    (*sibling).color = Color::Black;
+                    (*parent).color = Color::Red;
+                    rotate_right(root, parent);
+                    sibling = (*parent).left;
+                }
+
+                if !is_red((*sibling).left) && !is_red((*sibling).right) {
+                    (*sibling).color = Color::Red;
+                    node = parent;
+                    parent = (*node).parent;
+                    continue;
+                }
+
+                if !is_red((*sibling).left) {
+                    (*(*sibling).right).color = Color::Black;
+                    (*sibling).color = Color::Red;
+                    rotate_left(root, sibling);
+                    sibling = (*parent).left;
+                }
+                (*sibling).color = (*parent).color;
+                (*parent).color = Color::Black;
+                (*(*sibling).left).color = Color::Black;
+                rotate_right(root, parent);
+            }
+
+            node = *root;
+        }
+
+        if !node.is_null() {
+            (*node).color = Color::Black;
+        }
+    }
+}
+
+/// Checks the subtree rooted at `node` and returns its black height and number
+/// of nodes. `less` must hold between every node and the nodes after it
+unsafe fn check_node<T, F>(node: *mut Node<T>, parent: *mut Node<T>, less: &F) -> (usize, usize)
+where
+    F: Fn(&T, &T) -> bool,
+{
+    if node.is_null() {
+        return (1, 0);
+    }
+
+    unsafe {
+        assert!((*node).parent == parent, "red-black parent link is broken");
+        if is_red(node) {
+            assert!(
+                !is_red((*node).left) && !is_red((*node).right),
+                "red-black red node has a red child"
+            );
+        }
+        if let Some(left) = (*node).left.as_ref() {
+            assert!(
+                less(&left.data, &(*node).data),
+                "red-black ordering violated"
+            );
+        }
+        if let Some(right) = (*node).right.as_ref() {
+            assert!(
+                less(&(*node).data, &right.data),
+                "red-black ordering violated"
+            );
+        }
+
+        let (left_black, left_len) = check_node((*node).left, node, less);
+        let (right_black, right_len) = check_node((*node).right, node, less);
+        assert_eq!(left_black, right_black, "red-black black height differs");
+
+        let black = left_black + usize::from((*node).color == Color::Black);
+        (black, 1 + left_len + right_len)
+    }
+}
+
+/// Shared validator of [`RedBlackTree`] and [`RedBlackMap`]. Besides the local
+/// checks done by [`check_node`], the in order walk must be strictly
+/// increasing, which catches ordering violations between distant nodes
+unsafe fn assert_invariants<T, F>(root: *mut Node<T>, len: usize, less: F)
+where
+    F: Fn(&T, &T) -> bool,
+{
+    unsafe {
+        assert!(!is_red(root), "red-black root is red");
+        let (_, count) = check_node(root, ptr::null_mut(), &less);
+        assert_eq!(count, len, "red-black length is stale");
+
+        let mut iter = Iter::<T>::new(root, len);
+        if let Some(mut previous) = iter.next() {
+            for data in iter {
+                assert!(less(previous, data), "red-black ordering violated");
+                previous = data;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::live_allocations;
+    use crate::xorshift::XorShift;
+    use std::collections::BTreeSet;
+
+    fn sample_tree() -> RedBlackTree<i32> {
+        let mut tree = RedBlackTree::new(10);
+        for data in [5, 1, 9, 15, 30, 11] {
+            tree.add(data);
+            tree.assert_invariants();
+        }
+        tree
+    }
+
+    #[test]
+    fn find_node() {
+        let tree = sample_tree();
+        assert_eq!(tree.get(&10), Some(&10));
+        assert_eq!(tree.get(&30), Some(&30));
+        assert_eq!(tree.get(&2), None);
+        assert_eq!(tree.len(), 7);
+    }
+
+    #[test]
+    fn traversals_check() {
+        let tree = sample_tree();
+
+        let mut vals = vec![];
+        tree.in_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, vec![1, 5, 9, 10, 11, 15, 30]);
+        assert!(tree.iter().eq(vals.iter()));
+        assert!(tree.iter().rev().eq(vals.iter().rev()));
+
+        let mut vals = vec![];
+        tree.pre_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, vec![5, 1, 10, 9, 15, 11, 30]);
+
+        let mut vals = vec![];
+        tree.post_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, vec![1, 9, 11, 30, 15, 10, 5]);
+    }
+
+    #[test]
+    fn traversals_match_recursive_walk() {
+        fn walk(node: *mut Node<u64>, pre: &mut Vec<u64>, post: &mut Vec<u64>) {
+            if let Some(node) = unsafe { node.as_ref() } {
+                pre.push(node.data);
+                walk(node.left, pre, post);
+                walk(node.right, pre, post);
+                post.push(node.data);
+            }
+        }
+
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+        let mut tree = RedBlackTree::new(500);
+        for _ in 0..2_000 {
+            tree.add(rng.next() % 1_000);
+        }
+        for _ in 0..500 {
+            tree.delete(&(rng.next() % 1_000));
+        }
+
+        let (mut pre, mut post) = (vec![], vec![]);
+        walk(tree.root, &mut pre, &mut post);
+        let mut vals = vec![];
+        tree.pre_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, pre);
+        let mut vals = vec![];
+        tree.post_order(&mut |&data| vals.push(data));
+        assert_eq!(vals, post);
+    }
+
+    #[test]
+    fn delete_check() {
+        let mut tree = sample_tree();
+        tree.delete(&10);
+        tree.assert_invariants();
+        assert_eq!(tree.get(&10), None);
+        tree.delete(&42);
+        assert_eq!(tree.len(), 6);
+
+        for data in [5, 1, 9, 15, 30, 11] {
+            tree.delete(&data);
+            tree.assert_invariants();
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.iter().next(), None);
+
+        tree.add(3);
+        tree.assert_invariants();
+        assert_eq!(tree.into_iter().collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn nan_is_never_matched() {
+        let mut tree = RedBlackTree::new(2.0);
+        tree.add(1.0);
+        tree.add(3.0);
+        tree.add(f64::NAN);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&f64::NAN), None);
+
+        // Deleting NaN leaves every value, the root included, in place
+        tree.delete(&f64::NAN);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&1.0, &2.0, &3.0]);
+        tree.assert_invariants();
+    }
+
+    #[test]
+    fn sorted_keys_stay_balanced() {
+        let mut tree = RedBlackTree::new(0);
+        for data in 1..100_000 {
+            tree.add(data);
+        }
+        tree.assert_invariants();
+        // Paths are at most 2 log2(n + 1) long
+        let bound = 2.0 * ((tree.len() + 1) as f64).log2();
+        assert!(tree.height() as f64 <= bound);
+
+        for data in (0..100_000).step_by(3) {
+            tree.delete(&data);
+        }
+        tree.assert_invariants();
+        let bound = 2.0 * ((tree.len() + 1) as f64).log2();
+        assert!(tree.height() as f64 <= bound);
+        assert!(tree
+            .iter()
+            .copied()
+            .eq((0..100_000).filter(|data| data % 3 != 0)));
+    }
+
+    #[test]
+    fn matches_btreeset() {
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+
+        let mut tree = RedBlackTree::new(250);
+        let mut expected = BTreeSet::from([250]);
+        for _ in 0..5_000 {
+            let data = rng.next() % 500;
+            if rng.next().is_multiple_of(2) {
+                tree.delete(&data);
+                expected.remove(&data);
+            } else {
+                tree.add(data);
+                expected.insert(data);
+            }
+            tree.assert_invariants();
+            assert_eq!(tree.get(&data), expected.get(&data));
+        }
+
+        assert!(tree.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    #[should_panic(expected = "red node has a red child")]
+    fn validator_catches_red_red() {
+        let tree = sample_tree();
+        unsafe {
+            // 15, below the root's right child 10
+            let node = (*(*tree.root).right).right;
+            (*node).color = Color::Red;
+            (*(*node).left).color = Color::Red;
+        }
+        tree.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "black height differs")]
+    fn validator_catches_black_height() {
+        let tree = sample_tree();
+        unsafe {
+            let mut node = minimum(tree.root);
+            while !is_red(node) {
+                node = successor(node);
+            }
+            (*node).color = Color::Black;
+        }
+        tree.assert_invariants();
+    }
+
+    #[test]
+    fn drop_frees_every_node() {
+        let before = live_allocations();
+        {
+            let mut tree = RedBlackTree::new(String::from("m"));
+            for data in ["f", "t", "a", "h", "p", "z", "b"] {
+                tree.add(String::from(data));
+            }
+            tree.delete(&String::from("m"));
+            tree.delete(&String::from("a"));
+            let mut iter = tree.into_iter();
+            iter.next();
+        }
+        assert_eq!(live_allocations(), before);
+    }
+}
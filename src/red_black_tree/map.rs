@@ -0,0 +1,501 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+
+use super::{
+    free_subtree, insert_at, maximum, minimum, predecessor, remove_node, search, successor, Found,
+    Node,
+};
+
+/// Ordered key/value map built on the same nodes as
+/// [`RedBlackTree`](super::RedBlackTree).
+///
+/// Insertion, lookup, removal and iteration use the same method names as
+/// [`BstMap`](crate::binary_search_tree::BstMap), so code limited to those can
+/// swap one for the other behind a type alias. The entry API and range
+/// queries are only on `BstMap`. Lookups accept any borrowed form of the key,
+/// like the maps in `std::collections`.
+///
+/// ### Examples
+///
+/// ```
+/// use dsa_abc::red_black_tree::RedBlackMap;
+///
+/// let mut map = RedBlackMap::new_empty();
+/// for (key, value) in [(3, "three"), (1, "one"), (2, "two")] {
+///     map.insert(key, value);
+/// }
+///
+/// assert_eq!(map.get(&2), Some(&"two"));
+/// assert_eq!(map.remove(&1), Some("one"));
+/// assert_eq!(map.keys().collect::<Vec<_>>(), vec![&2, &3]);
+/// ```
+///
+pub struct RedBlackMap<K, V> {
+    root: *mut Node<(K, V)>,
+    len: usize,
+}
+
+impl<K: Ord, V> RedBlackMap<K, V> {
+    /// Creates a new map with an initial entry as root
+    pub fn new(key: K, value: V) -> RedBlackMap<K, V> {
+        let mut map = Self::new_empty();
+        map.insert(key, value);
+        map
+    }
+
+    /// Creates a new empty map
+    pub fn new_empty() -> RedBlackMap<K, V> {
+        RedBlackMap {
+            root: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of entries in the map. O(1) time complexity
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map holds no entries. O(1) time complexity
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Compares `key` with the key of a node's entry. Keys are totally
+    /// ordered, so the result is never `None`
+    fn compare<Q>(key: &Q, entry: &(K, V)) -> Option<Ordering>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Some(key.cmp(entry.0.borrow()))
+    }
+
+    /// Finds the node holding `key`, or the parent under which it belongs
+    /// together with the side it goes to
+    fn search<Q>(&self, key: &Q) -> Found<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        unsafe {
+            search(self.root, |entry| Self::compare(key, entry))
+                .expect("totally ordered keys always have a place in the tree")
+        }
+    }
+
+    /// Finds the node holding `key`, or a null pointer
+    fn find_node<Q>(&self, key: &Q) -> *mut Node<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).unwrap_or(ptr::null_mut())
+    }
+
+    /// Inserts `value` under `key`. If the key was already present its value
+    /// is replaced and the old one returned, keeping the original key.
+    /// O(log n) time complexity
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        unsafe {
+            match self.search(&key) {
+                Ok(node) => Some(mem::replace(&mut (*node).data.1, value)),
+                Err((parent, side)) => {
+                    insert_at(ptr::addr_of_mut!(self.root), parent, side, (key, value));
+                    self.len += 1;
+                    None
+                }
+            }
+        }
+    }
+
+    /// Returns a reference to the value stored under `key`.
+    /// O(log n) time complexity
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        unsafe { self.find_node(key).as_ref().map(|node| &node.data.1) }
+    }
+
+    /// Returns a mutable reference to the value stored under `key`.
+    /// O(log n) time complexity
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        unsafe { self.find_node(key).as_mut().map(|node| &mut node.data.1) }
+    }
+
+    /// Returns `true` if the map holds an entry for `key`.
+    /// O(log n) time complexity
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        !self.find_node(key).is_null()
+    }
+
+    /// Removes the entry for `key` and returns its value, if any.
+    /// O(log n) time complexity
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = self.find_node(key);
+        if node.is_null() {
+            return None;
+        }
+
+        self.len -= 1;
+        unsafe { Some(remove_node(ptr::addr_of_mut!(self.root), node).1) }
+    }
+
+    /// Returns an iterator over the entries of the map, ordered by key
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(super::Iter::new(self.root, self.len))
+    }
+
+    /// Returns an iterator over the entries of the map, ordered by key, with
+    /// mutable references to the values
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut::new(self.root, self.len)
+    }
+
+    /// Returns an iterator over the keys of the map, in order
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys(self.iter())
+    }
+
+    /// Returns an iterator over the values of the map, ordered by key
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values(self.iter())
+    }
+
+    /// Returns an iterator over mutable references to the values of the map,
+    /// ordered by key
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut(self.iter_mut())
+    }
+
+    /// Panics if any red-black invariant is broken, like
+    /// [`RedBlackTree::assert_invariants`](super::RedBlackTree::assert_invariants).
+    /// Keys must be strictly increasing. O(n) time complexity
+    pub fn assert_invariants(&self) {
+        unsafe { super::assert_invariants(self.root, self.len, |a, b| a.0 < b.0) }
+    }
+}
+
+impl<K, V> Drop for RedBlackMap<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            free_subtree(self.root);
+        }
+        self.root = ptr::null_mut();
+        self.len = 0;
+    }
+}
+
+impl<K, V> IntoIterator for RedBlackMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// Consumes the map into an iterator over its entries, ordered by key
+    fn into_iter(mut self) -> IntoIter<K, V> {
+        let root = self.root;
+        let len = self.len;
+        self.root = ptr::null_mut();
+        self.len = 0;
+        IntoIter(super::IntoIter::new(root, len))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a RedBlackMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        Iter(super::Iter::new(self.root, self.len))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut RedBlackMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        IterMut::new(self.root, self.len)
+    }
+}
+
+/// Iterator over the entries of a [`RedBlackMap`], created by
+/// [`RedBlackMap::iter`]
+pub struct Iter<'a, K, V>(super::Iter<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+/// Iterator over the entries of a [`RedBlackMap`] with mutable references to
+/// the values, created by [`RedBlackMap::iter_mut`]
+pub struct IterMut<'a, K, V> {
+    front: *mut Node<(K, V)>,
+    back: *mut Node<(K, V)>,
+    remaining: usize,
+    marker: PhantomData<&'a mut (K, V)>,
+}
+
+impl<'a, K, V> IterMut<'a, K, V> {
+    fn new(root: *mut Node<(K, V)>, len: usize) -> IterMut<'a, K, V> {
+        unsafe {
+            IterMut {
+                front: minimum(root),
+                back: maximum(root),
+                remaining: len,
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        unsafe {
+            let node = self.front;
+            self.front = successor(node);
+            self.remaining -= 1;
+            Some((&(*node).data.0, &mut (*node).data.1))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        unsafe {
+            let node = self.back;
+            self.back = predecessor(node);
+            self.remaining -= 1;
+            Some((&(*node).data.0, &mut (*node).data.1))
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+/// Iterator over the keys of a [`RedBlackMap`], created by
+/// [`RedBlackMap::keys`]
+pub struct Keys<'a, K, V>(Iter<'a, K, V>);
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.0.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.0.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+/// Iterator over the values of a [`RedBlackMap`], created by
+/// [`RedBlackMap::values`]
+pub struct Values<'a, K, V>(Iter<'a, K, V>);
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.0.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.0.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+/// Iterator over mutable references to the values of a [`RedBlackMap`],
+/// created by [`RedBlackMap::values_mut`]
+pub struct ValuesMut<'a, K, V>(IterMut<'a, K, V>);
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.0.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.0.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+/// Owning iterator over the entries of a [`RedBlackMap`], ordered by key,
+/// created by its [`IntoIterator`] implementation
+pub struct IntoIter<K, V>(super::IntoIter<(K, V)>);
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::live_allocations;
+    use crate::xorshift::XorShift;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut map = RedBlackMap::new(10, "ten");
+        for (key, value) in [(5, "five"), (15, "fifteen"), (1, "one")] {
+            assert_eq!(map.insert(key, value), None);
+            map.assert_invariants();
+        }
+        assert_eq!(map.insert(5, "FIVE"), Some("five"));
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(&5), Some(&"FIVE"));
+        assert!(map.contains_key(&15));
+
+        *map.get_mut(&1).unwrap() = "uno";
+        assert_eq!(map.get(&1), Some(&"uno"));
+
+        assert_eq!(map.remove(&10), Some("ten"));
+        assert_eq!(map.remove(&10), None);
+        map.assert_invariants();
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![1, 5, 15]);
+    }
+
+    #[test]
+    fn iters_check() {
+        let mut map = RedBlackMap::new_empty();
+        for key in ["b", "d", "a", "c"] {
+            map.insert(String::from(key), key.len());
+        }
+
+        assert_eq!(map.get("c"), Some(&1));
+        assert_eq!(
+            map.keys().rev().collect::<Vec<_>>(),
+            vec!["d", "c", "b", "a"]
+        );
+
+        for value in map.values_mut() {
+            *value *= 10;
+        }
+        for (key, value) in &mut map {
+            if key.as_str() > "b" {
+                *value += 1;
+            }
+        }
+        assert_eq!(
+            map.values().copied().collect::<Vec<_>>(),
+            vec![10, 10, 11, 11]
+        );
+
+        let entries: Vec<(String, usize)> = map.into_iter().collect();
+        assert_eq!(entries.first(), Some(&(String::from("a"), 10)));
+    }
+
+    #[test]
+    fn matches_btreemap() {
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+
+        let mut map = RedBlackMap::new_empty();
+        let mut expected = BTreeMap::new();
+        for step in 0..3_000u64 {
+            let key = rng.next() % 300;
+            if rng.next().is_multiple_of(2) {
+                assert_eq!(map.remove(&key), expected.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, step), expected.insert(key, step));
+            }
+            map.assert_invariants();
+        }
+
+        assert!(map.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn drop_frees_every_node() {
+        let before = live_allocations();
+        {
+            let mut map = RedBlackMap::new_empty();
+            for key in ["m", "f", "t", "a", "h"] {
+                map.insert(String::from(key), key.repeat(2));
+            }
+            map.remove("f");
+        }
+        assert_eq!(live_allocations(), before);
+    }
+}
@@ -1,3 +1,4 @@
+use std::marker::PhantomData;
 use std::ptr;
 
 /// Linked list which nodes only point to their next element.
@@ -110,6 +111,10 @@ impl<T: PartialEq> SinglyLinkedList<T> {
 
     /// Removes the last element of the list. O(n) time complexity
     pub fn pop(&mut self) {
+        if self.size == 0 {
+            return;
+        }
+
         if self.leaf == self.root {
             unsafe {
                 drop(Box::from_raw(self.root));
@@ -164,6 +169,10 @@ impl<T: PartialEq> SinglyLinkedList<T> {
     /// Removes the first element that matches `data` using PartialEq.
     /// O(n) time complexity
     pub fn remove_data(&mut self, data: T) {
+        if self.size == 0 {
+            return;
+        }
+
         unsafe {
             if (*self.root).data == data {
                 self.remove_first();
@@ -186,8 +195,9 @@ impl<T: PartialEq> SinglyLinkedList<T> {
 
             // current will always have a next, because we checked for the leaf
             // in the start
-            drop(Box::from_raw((*past).next));
             (*past).next = (*current).next;
+            drop(Box::from_raw(current));
+            self.size -= 1;
         }
     }
 
@@ -195,31 +205,32 @@ impl<T: PartialEq> SinglyLinkedList<T> {
     pub fn remove_at(&mut self, index: u32) {
         if self.size == 0 {
             return;
-        } else {
-            if index == 0 {
-                self.remove_first();
-                return;
-            } else if index == self.size - 1 {
-                self.pop();
-                return;
-            }
+        }
 
-            let mut current = self.root;
-            let mut pos: u32 = 0;
+        if index == 0 {
+            self.remove_first();
+            return;
+        } else if index == self.size - 1 {
+            self.pop();
+            return;
+        }
 
-            unsafe {
-                while !(*current).next.is_null() && pos < index {
-                    if pos == index - 1 {
-                        drop(Box::from_raw((*current).next));
-                        // current.next.next can be a null pointer, but it is
-                        // not a problem
-                        (*current).next = (*(*current).next).next;
-                        self.size -= 1;
-                        return;
-                    } else {
-                        current = (*current).next;
-                        pos += 1;
-                    }
+        let mut current = self.root;
+        let mut pos: u32 = 0;
+
+        unsafe {
+            while !(*current).next.is_null() && pos < index {
+                if pos == index - 1 {
+                    let removed = (*current).next;
+                    // removed.next can be a null pointer, but it is not a
+                    // problem
+                    (*current).next = (*removed).next;
+                    drop(Box::from_raw(removed));
+                    self.size -= 1;
+                    return;
+                } else {
+                    current = (*current).next;
+                    pos += 1;
                 }
             }
         }
@@ -269,6 +280,177 @@ impl<T: PartialEq> SinglyLinkedList<T> {
             }
         }
     }
+
+    /// Returns an iterator over references to the list's elements, from first
+    /// to last. O(1) time complexity per step
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            current: self.root,
+            remaining: self.size as usize,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over mutable references to the list's elements,
+    /// from first to last. O(1) time complexity per step
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            current: self.root,
+            remaining: self.size as usize,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Iterator over references to the elements of a [`SinglyLinkedList`],
+/// created by [`SinglyLinkedList::iter`]
+pub struct Iter<'a, T> {
+    current: *mut Node<T>,
+    remaining: usize,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.current.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let node = &*self.current;
+            self.current = node.next;
+            self.remaining -= 1;
+            Some(&node.data)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+/// Iterator over mutable references to the elements of a
+/// [`SinglyLinkedList`], created by [`SinglyLinkedList::iter_mut`]
+pub struct IterMut<'a, T> {
+    current: *mut Node<T>,
+    remaining: usize,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.current.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let node = &mut *self.current;
+            self.current = node.next;
+            self.remaining -= 1;
+            Some(&mut node.data)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+/// Owning iterator over the elements of a [`SinglyLinkedList`], created by
+/// its [`IntoIterator`] implementation. Nodes are freed as they are yielded
+pub struct IntoIter<T> {
+    list: SinglyLinkedList<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let list = &mut self.list;
+        if list.root.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let node = Box::from_raw(list.root);
+            list.root = node.next;
+            if list.root.is_null() {
+                list.leaf = ptr::null_mut();
+            }
+            list.size -= 1;
+            Some(node.data)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.list.size as usize;
+        (size, Some(size))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for SinglyLinkedList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { list: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SinglyLinkedList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        Iter {
+            current: self.root,
+            remaining: self.size as usize,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut SinglyLinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        IterMut {
+            current: self.root,
+            remaining: self.size as usize,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for SinglyLinkedList<T> {
+    /// Frees every node iteratively, following the `next` pointers.
+    /// O(n) time complexity
+    fn drop(&mut self) {
+        let mut current = self.root;
+
+        unsafe {
+            while !current.is_null() {
+                let next = (*current).next;
+                drop(Box::from_raw(current));
+                current = next;
+            }
+        }
+
+        self.root = ptr::null_mut();
+        self.leaf = ptr::null_mut();
+        self.size = 0;
+    }
 }
 
 struct Node<T> {
@@ -300,6 +482,7 @@ impl<T> Node<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::alloc_counter::live_allocations;
 
     #[test]
     fn find_first() {
@@ -393,4 +576,157 @@ mod tests {
         assert_eq!(list.get_last(), Some(&20));
         assert_eq!(list.size, 1);
     }
+
+    #[test]
+    fn remove_data_from_middle() {
+        let before = live_allocations();
+        {
+            let mut list = SinglyLinkedList::new(String::from("a"));
+            for data in ["b", "c", "d", "e"] {
+                list.push(String::from(data));
+            }
+
+            // The node is unlinked before it is freed, and the size follows
+            list.remove_data(String::from("c"));
+            assert_eq!(list.size, 4);
+            assert_eq!(list.iter().collect::<Vec<_>>(), vec!["a", "b", "d", "e"]);
+
+            list.remove_data(String::from("d"));
+            assert_eq!(list.size, 3);
+            assert_eq!(list.iter().collect::<Vec<_>>(), vec!["a", "b", "e"]);
+
+            // Values that aren't stored leave the list as it is
+            list.remove_data(String::from("z"));
+            assert_eq!(list.size, 3);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn remove_at_from_middle() {
+        let before = live_allocations();
+        {
+            let mut list = SinglyLinkedList::new(String::from("a"));
+            for data in ["b", "c", "d", "e"] {
+                list.push(String::from(data));
+            }
+
+            // The node is unlinked before it is freed
+            list.remove_at(2);
+            assert_eq!(list.size, 4);
+            assert_eq!(list.iter().collect::<Vec<_>>(), vec!["a", "b", "d", "e"]);
+
+            list.remove_at(1);
+            assert_eq!(list.size, 3);
+            assert_eq!(list.iter().collect::<Vec<_>>(), vec!["a", "d", "e"]);
+            assert_eq!(list.get_last().map(String::as_str), Some("e"));
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn remove_from_empty_list() {
+        let mut list = SinglyLinkedList::<i32>::new_empty();
+        list.pop();
+        list.remove_data(10);
+        list.remove_at(0);
+        assert_eq!(list.size, 0);
+        assert_eq!(list.get_first(), None);
+
+        list.push(10);
+        assert_eq!(list.get_last(), Some(&10));
+    }
+
+    #[test]
+    fn drop_frees_every_node() {
+        let before = live_allocations();
+        {
+            let mut list = SinglyLinkedList::new(String::from("a"));
+            list.push(String::from("b"));
+            list.push(String::from("c"));
+            list.insert(String::from("z"));
+            list.remove_at(1);
+            list.remove_data(String::from("b"));
+            assert!(live_allocations() > before);
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn drop_long_list() {
+        let before = live_allocations();
+        {
+            let mut list = SinglyLinkedList::new_empty();
+            for i in 0..1_000_000 {
+                list.push(i);
+            }
+        }
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn iter_check() {
+        let mut list = SinglyLinkedList::new(10);
+        list.push(20);
+        list.push(30);
+        list.insert(1);
+
+        assert_eq!(
+            list.iter().copied().collect::<Vec<_>>(),
+            vec![1, 10, 20, 30]
+        );
+        assert_eq!(list.iter().len(), 4);
+        assert_eq!(list.iter().sum::<i32>(), 61);
+        assert_eq!(list.iter().filter(|&&data| data > 5).count(), 3);
+
+        let mut total = 0;
+        for data in &list {
+            total += data;
+        }
+        assert_eq!(total, 61);
+
+        let list = SinglyLinkedList::<i32>::new_empty();
+        assert_eq!(list.iter().next(), None);
+    }
+
+    #[test]
+    fn iter_mut_check() {
+        let mut list = SinglyLinkedList::new(10);
+        list.push(20);
+        list.push(30);
+
+        for data in list.iter_mut() {
+            *data *= 2;
+        }
+        for data in &mut list {
+            *data += 1;
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![21, 41, 61]);
+        assert_eq!(list.get_last(), Some(&61));
+    }
+
+    #[test]
+    fn into_iter_check() {
+        let before = live_allocations();
+        {
+            let mut list = SinglyLinkedList::new(String::from("a"));
+            list.push(String::from("b"));
+            list.push(String::from("c"));
+
+            let mut iter = list.into_iter();
+            assert_eq!(iter.next().as_deref(), Some("a"));
+            assert_eq!(iter.len(), 2);
+            assert_eq!(iter.collect::<Vec<_>>(), vec!["b", "c"]);
+        }
+        assert_eq!(live_allocations(), before);
+
+        {
+            let mut list = SinglyLinkedList::new(String::from("a"));
+            list.push(String::from("b"));
+            let mut iter = list.into_iter();
+            iter.next();
+            // Dropping the iterator frees the nodes that were not yielded
+        }
+        assert_eq!(live_allocations(), before);
+    }
 }
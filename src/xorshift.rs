@@ -0,0 +1,15 @@
+//! Small xorshift generator shared by the randomized tests, so their runs are
+//! reproducible without pulling in a dependency.
+
+/// Xorshift generator seeded with any non-zero value
+pub(crate) struct XorShift(pub(crate) u64);
+
+impl XorShift {
+    /// Advances the state and returns it
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}